@@ -0,0 +1,189 @@
+use crate::detector::{CameraIntrinsics, Pose, Rotation, TagDetection, Translation};
+use crate::error::Error;
+use crate::family::TagFamily;
+use crate::pnp::{self, TAG_CORNERS};
+use crate::refine::RefineOptions;
+use nalgebra::Vector3;
+use std::collections::BTreeMap;
+
+/// A rigid set of tags with known positions, such as a printed grid or a field wall.
+///
+/// Each tag is stored as the board coordinates of its four corners, in the same order as
+/// `TagDetection::corners`: bottom-left, bottom-right, top-right, top-left, as seen from in front of
+/// the tag. Units are up to the caller, and the estimated translation comes out in the same units.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TagBoard {
+    family: TagFamily,
+    tags: BTreeMap<u32, [[f64; 3]; 4]>,
+}
+
+/// The result of `TagBoard::estimate_pose`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoardPose {
+    /// Pose of the board frame in the camera frame.
+    pub pose: Pose,
+    /// Root-mean-square reprojection error over every corner used, in pixels.
+    pub error: f64,
+    /// Ids of the detections that contributed, in the order they were given.
+    pub ids: Vec<u32>,
+}
+
+impl TagBoard {
+    pub fn new(family: TagFamily) -> TagBoard {
+        TagBoard {
+            family,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// A flat `rows` x `cols` grid of tags in the board's z = 0 plane, numbered row by row from
+    /// `first_id`. The board frame matches the tag frame: the top-left tag's top-left corner is at
+    /// the origin, x runs across the columns and y down the rows.
+    pub fn grid(family: TagFamily, rows: u32, cols: u32, tag_size: f64, spacing: f64, first_id: u32) -> TagBoard {
+        TagBoard::try_grid(family, rows, cols, tag_size, spacing, first_id).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_grid(family: TagFamily, rows: u32, cols: u32, tag_size: f64, spacing: f64, first_id: u32) -> Result<TagBoard, Error> {
+        let last = rows.checked_mul(cols)
+            .and_then(|count| first_id.checked_add(count.saturating_sub(1)));
+        if last.is_none() {
+            return Err(Error::InvalidConfig { field: "first_id", reason: "the grid's last id doesn't fit in a u32" });
+        }
+        let mut board = TagBoard::new(family);
+        let pitch = tag_size + spacing;
+        for row in 0..rows {
+            for col in 0..cols {
+                let center = [
+                    col as f64 * pitch + tag_size / 2.0,
+                    row as f64 * pitch + tag_size / 2.0,
+                ];
+                let corners = TAG_CORNERS.map(|[x, y]| {
+                    [center[0] + x * tag_size / 2.0, center[1] + y * tag_size / 2.0, 0.0]
+                });
+                board.add_tag(first_id + row * cols + col, corners);
+            }
+        }
+        Ok(board)
+    }
+
+    pub fn family(&self) -> TagFamily {
+        self.family
+    }
+
+    /// Adds a tag, replacing any tag already on the board with the same id.
+    pub fn add_tag(&mut self, id: u32, corners: [[f64; 3]; 4]) {
+        self.tags.insert(id, corners);
+    }
+
+    /// Adds a square tag of side `tag_size`, where `pose` is the tag's frame in board coordinates.
+    pub fn add_tag_with_pose(&mut self, id: u32, pose: &Pose, tag_size: f64) {
+        let rotation = pose.rot.matrix();
+        let translation = Vector3::new(pose.pos.x, pose.pos.y, pose.pos.z);
+        let corners = TAG_CORNERS.map(|[x, y]| {
+            let p = rotation * Vector3::new(x * tag_size / 2.0, y * tag_size / 2.0, 0.0) + translation;
+            [p.x, p.y, p.z]
+        });
+        self.add_tag(id, corners);
+    }
+
+    pub fn remove_tag(&mut self, id: u32) -> Option<[[f64; 3]; 4]> {
+        self.tags.remove(&id)
+    }
+
+    pub fn corners(&self, id: u32) -> Option<&[[f64; 3]; 4]> {
+        self.tags.get(&id)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.tags.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Solves for the board's pose from every detection that belongs to it, ignoring the rest.
+    ///
+    /// Each matching tag is first solved on its own with libapriltag, and both of its candidate
+    /// poses are carried over to the board as starting points. Every starting point is then refined
+    /// against all matched corners at once, and the one with the lowest reprojection error wins. A
+    /// single tag can still be ambiguous, but with two or more tags the flipped solutions rarely
+    /// survive.
+    ///
+    /// With a distorted camera, the corners are undistorted first and the error is measured in
+    /// undistorted pixels.
+    pub fn estimate_pose(&self, detections: &[TagDetection], intrinsics: &CameraIntrinsics) -> Result<BoardPose, Error> {
+        let mut matched: Vec<(TagDetection, &[[f64; 3]; 4])> = Vec::new();
+        for det in detections.iter().filter(|det| det.family == self.family) {
+            if let Some(corners) = self.tags.get(&det.id) {
+                matched.push((det.undistorted(intrinsics)?, corners));
+            }
+        }
+        let intrinsics = &intrinsics.pinhole();
+        if matched.is_empty() {
+            return Err(Error::DegeneratePose("no detections belong to the board"));
+        }
+
+        let mut object = Vec::with_capacity(matched.len() * 4);
+        let mut image = Vec::with_capacity(matched.len() * 4);
+        for (det, corners) in &matched {
+            object.extend(corners.iter().map(|c| Vector3::new(c[0], c[1], c[2])));
+            image.extend_from_slice(&det.corners);
+        }
+
+        let mut best: Option<pnp::Solution> = None;
+        for (det, corners) in &matched {
+            for (rotation, translation) in initial_guesses(det, corners, intrinsics) {
+                let Some(solution) = pnp::refine(&object, &image, intrinsics, rotation, translation, &RefineOptions::default()) else {
+                    continue;
+                };
+                if best.is_none_or(|b| solution.rms < b.rms) {
+                    best = Some(solution);
+                }
+            }
+        }
+        let best = best.ok_or(Error::DegeneratePose("no starting pose put the board in front of the camera"))?;
+
+        Ok(BoardPose {
+            pose: Pose {
+                rot: Rotation::from_matrix(best.rotation)?,
+                pos: Translation {
+                    x: best.translation.x,
+                    y: best.translation.y,
+                    z: best.translation.z,
+                },
+            },
+            error: best.rms,
+            ids: matched.iter().map(|(det, _)| det.id).collect(),
+        })
+    }
+}
+
+// Board-in-camera poses implied by each single-tag solution for `det`.
+fn initial_guesses(det: &TagDetection, corners: &[[f64; 3]; 4], intrinsics: &CameraIntrinsics) -> Vec<(nalgebra::Matrix3<f64>, Vector3<f64>)> {
+    let board: Vec<Vector3<f64>> = corners.iter().map(|c| Vector3::new(c[0], c[1], c[2])).collect();
+    let tag_size = (0..4).map(|i| (board[(i + 1) % 4] - board[i]).norm()).sum::<f64>() / 4.0;
+    let model = pnp::tag_corners(tag_size);
+    // Where the tag sits on the board, for a tag that isn't exactly square this is a best fit
+    let Some((board_rotation, board_translation)) = pnp::fit_rigid(&model, &board) else {
+        return Vec::new();
+    };
+    let Ok(estimate) = det.estimate_pose_candidates(intrinsics, tag_size, 50) else {
+        return Vec::new();
+    };
+
+    std::iter::once(estimate.best)
+        .chain(estimate.alternate.map(|(pose, _)| pose))
+        .map(|tag| {
+            let rotation = tag.rot.matrix() * board_rotation.transpose();
+            let translation = Vector3::new(tag.pos.x, tag.pos.y, tag.pos.z) - rotation * board_translation;
+            (rotation, translation)
+        })
+        .collect()
+}
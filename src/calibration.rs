@@ -0,0 +1,409 @@
+use crate::board::TagBoard;
+use crate::detector::{CameraIntrinsics, Detector, Pose, Rotation, TagDetection, Translation};
+use crate::distortion::Distortion;
+use crate::error::Error;
+use crate::image::ImageU8;
+use nalgebra::{DMatrix, DVector, Matrix3, Rotation3, SymmetricEigen, Vector3};
+
+/// Which lens model `Calibrator` fits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DistortionModel {
+    None,
+    BrownConrady,
+    KannalaBrandt,
+}
+
+/// Solves for camera intrinsics from several views of a flat `TagBoard`.
+///
+/// The board's tags must all lie in its z = 0 plane, as they do for `TagBoard::grid`. An initial
+/// pinhole estimate comes from Zhang's method, then the intrinsics, distortion and every board
+/// pose are refined together with Levenberg-Marquardt.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibrator {
+    pub board: TagBoard,
+    pub model: DistortionModel,
+    /// Frames with fewer board tags than this are skipped.
+    pub min_tags: usize,
+    pub max_iterations: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CalibrationFrame {
+    /// Position of the frame in the input.
+    pub index: usize,
+    /// Pose of the board in the camera frame.
+    pub pose: Pose,
+    /// Root-mean-square reprojection error over this frame's corners, in pixels.
+    pub rms: f64,
+    pub tags: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Calibration {
+    pub intrinsics: CameraIntrinsics,
+    /// Root-mean-square reprojection error over every frame used, in pixels.
+    pub rms: f64,
+    /// The frames that were used. Skipped frames are left out.
+    pub frames: Vec<CalibrationFrame>,
+}
+
+// Corners of one frame: board points in the z = 0 plane and where they were seen.
+struct Observations {
+    index: usize,
+    tags: usize,
+    object: Vec<Vector3<f64>>,
+    image: Vec<[f64; 2]>,
+}
+
+impl Calibrator {
+    pub fn new(board: TagBoard) -> Calibrator {
+        Calibrator {
+            board,
+            model: DistortionModel::BrownConrady,
+            min_tags: 4,
+            max_iterations: 100,
+        }
+    }
+
+    /// Detects the board in every image and calibrates from the results. `detector` must already
+    /// have the board's family added.
+    pub fn calibrate<T: AsRef<[u8]>>(&self, detector: &mut Detector, images: &[ImageU8<T>]) -> Result<Calibration, Error> {
+        let frames: Vec<Vec<TagDetection>> = images.iter()
+            .map(|image| detector.detect(image.view(0, 0, image.width(), image.height())))
+            .collect();
+        self.calibrate_detections(&frames)
+    }
+
+    /// Calibrates from detections that were already made, one list per frame.
+    pub fn calibrate_detections(&self, frames: &[Vec<TagDetection>]) -> Result<Calibration, Error> {
+        if self.board.ids().any(|id| self.board.corners(id).is_some_and(|c| c.iter().any(|p| p[2].abs() > 1e-9))) {
+            return Err(Error::CalibrationFailed("board tags must all lie in its z = 0 plane"));
+        }
+
+        let observations: Vec<Observations> = frames.iter().enumerate()
+            .filter_map(|(index, detections)| {
+                let mut obs = Observations { index, tags: 0, object: Vec::new(), image: Vec::new() };
+                for det in detections.iter().filter(|det| det.family == self.board.family()) {
+                    if let Some(corners) = self.board.corners(det.id) {
+                        obs.tags += 1;
+                        obs.object.extend(corners.iter().map(|c| Vector3::new(c[0], c[1], 0.0)));
+                        obs.image.extend_from_slice(&det.corners);
+                    }
+                }
+                (obs.tags >= self.min_tags.max(1)).then_some(obs)
+            })
+            .collect();
+        if observations.len() < 3 {
+            return Err(Error::CalibrationFailed("at least three frames with enough board tags are needed"));
+        }
+
+        let homographies = observations.iter()
+            .map(|obs| fit_homography(&obs.object, &obs.image))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::CalibrationFailed("a frame's corners don't determine a homography"))?;
+        let k = zhang_intrinsics(&homographies)
+            .ok_or(Error::CalibrationFailed("views are too similar to determine the intrinsics"))?;
+        let poses: Vec<(Matrix3<f64>, Vector3<f64>)> = homographies.iter()
+            .map(|h| board_pose(&k, h))
+            .collect();
+
+        let initial = Model {
+            intrinsics: CameraIntrinsics::new(k[(0, 0)], k[(1, 1)], k[(0, 2)], k[(1, 2)])
+                .with_distortion(match self.model {
+                    DistortionModel::None => Distortion::None,
+                    DistortionModel::BrownConrady => Distortion::BrownConrady { k1: 0.0, k2: 0.0, k3: 0.0, p1: 0.0, p2: 0.0 },
+                    // Zero coefficients would be the equidistant model, so start from the leading
+                    // terms of tan(theta) instead, which is the pinhole camera Zhang's method fit
+                    DistortionModel::KannalaBrandt => Distortion::KannalaBrandt {
+                        k1: 1.0 / 3.0,
+                        k2: 2.0 / 15.0,
+                        k3: 17.0 / 315.0,
+                        k4: 62.0 / 2835.0,
+                    },
+                }),
+            poses,
+        };
+        let refined = refine(&observations, initial, self.max_iterations);
+
+        let mut total = 0.0;
+        let mut count = 0;
+        let mut out = Vec::with_capacity(observations.len());
+        for (obs, (rotation, translation)) in observations.iter().zip(&refined.poses) {
+            let squared = squared_error(&refined.intrinsics, rotation, translation, obs);
+            total += squared;
+            count += obs.object.len();
+            out.push(CalibrationFrame {
+                index: obs.index,
+                pose: Pose {
+                    rot: Rotation::from_matrix(*rotation)?,
+                    pos: Translation { x: translation.x, y: translation.y, z: translation.z },
+                },
+                rms: (squared / obs.object.len() as f64).sqrt(),
+                tags: obs.tags,
+            });
+        }
+
+        Ok(Calibration {
+            intrinsics: refined.intrinsics,
+            rms: (total / count as f64).sqrt(),
+            frames: out,
+        })
+    }
+}
+
+// Where a board point lands in the image, or None if it's behind the camera.
+fn reproject(intrinsics: &CameraIntrinsics, rotation: &Matrix3<f64>, translation: &Vector3<f64>, point: &Vector3<f64>) -> Option<[f64; 2]> {
+    let c = rotation * point + translation;
+    if c.z <= f64::EPSILON {
+        return None;
+    }
+    let [x, y] = intrinsics.distortion.distort([c.x / c.z, c.y / c.z]);
+    Some([intrinsics.fx * x + intrinsics.cx, intrinsics.fy * y + intrinsics.cy])
+}
+
+fn squared_error(intrinsics: &CameraIntrinsics, rotation: &Matrix3<f64>, translation: &Vector3<f64>, obs: &Observations) -> f64 {
+    obs.object.iter().zip(&obs.image).map(|(x, seen)| {
+        match reproject(intrinsics, rotation, translation, x) {
+            Some(p) => (p[0] - seen[0]).powi(2) + (p[1] - seen[1]).powi(2),
+            None => f64::INFINITY,
+        }
+    }).sum()
+}
+
+// The board-plane-to-image homography, by DLT with Hartley normalization.
+fn fit_homography(object: &[Vector3<f64>], image: &[[f64; 2]]) -> Option<Matrix3<f64>> {
+    let normalize = |points: &mut dyn Iterator<Item = [f64; 2]>| -> Matrix3<f64> {
+        let points: Vec<[f64; 2]> = points.collect();
+        let n = points.len() as f64;
+        let (mx, my) = points.iter().fold((0.0, 0.0), |(x, y), p| (x + p[0] / n, y + p[1] / n));
+        let spread = points.iter().map(|p| ((p[0] - mx).powi(2) + (p[1] - my).powi(2)).sqrt()).sum::<f64>() / n;
+        let s = std::f64::consts::SQRT_2 / spread.max(f64::EPSILON);
+        Matrix3::new(s, 0.0, -s * mx, 0.0, s, -s * my, 0.0, 0.0, 1.0)
+    };
+    let to = |m: &Matrix3<f64>, p: [f64; 2]| {
+        let v = m * Vector3::new(p[0], p[1], 1.0);
+        [v.x / v.z, v.y / v.z]
+    };
+    let t_object = normalize(&mut object.iter().map(|p| [p.x, p.y]));
+    let t_image = normalize(&mut image.iter().copied());
+
+    let mut ata = DMatrix::<f64>::zeros(9, 9);
+    for (x, u) in object.iter().zip(image) {
+        let [x, y] = to(&t_object, [x.x, x.y]);
+        let [u, v] = to(&t_image, *u);
+        for row in [
+            [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, -u],
+            [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, -v],
+        ] {
+            let row = DVector::from_row_slice(&row);
+            ata += &row * row.transpose();
+        }
+    }
+    let h = smallest_eigenvector(ata);
+    let h = Matrix3::new(h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], h[8]);
+    let h = t_image.try_inverse()? * h * t_object;
+    h.iter().all(|v| v.is_finite()).then_some(h / h[(2, 2)])
+}
+
+fn smallest_eigenvector(m: DMatrix<f64>) -> DVector<f64> {
+    let eigen = SymmetricEigen::new(m);
+    let (smallest, _) = eigen.eigenvalues.iter().enumerate()
+        .fold((0, f64::INFINITY), |best, (i, &v)| if v < best.1 {(i, v)} else {best});
+    eigen.eigenvectors.column(smallest).into_owned()
+}
+
+// Zhang's closed-form camera matrix from three or more homographies. Skew is assumed to be zero,
+// so B12 is left out of the system instead of being pulled towards zero by an extra row, whose
+// weight would depend on the board's units. That also makes two views enough in principle.
+fn zhang_intrinsics(homographies: &[Matrix3<f64>]) -> Option<Matrix3<f64>> {
+    let v = |h: &Matrix3<f64>, i: usize, j: usize| {
+        DVector::from_row_slice(&[
+            h[(0, i)] * h[(0, j)],
+            h[(1, i)] * h[(1, j)],
+            h[(2, i)] * h[(0, j)] + h[(0, i)] * h[(2, j)],
+            h[(2, i)] * h[(1, j)] + h[(1, i)] * h[(2, j)],
+            h[(2, i)] * h[(2, j)],
+        ])
+    };
+    let mut vtv = DMatrix::<f64>::zeros(5, 5);
+    for h in homographies {
+        for row in [v(h, 0, 1), v(h, 0, 0) - v(h, 1, 1)] {
+            vtv += &row * row.transpose();
+        }
+    }
+
+    let mut b = smallest_eigenvector(vtv);
+    if b[0] < 0.0 {
+        b = -b;
+    }
+    let (b11, b12, b22, b13, b23, b33) = (b[0], 0.0, b[1], b[2], b[3], b[4]);
+    let denominator = b11 * b22 - b12 * b12;
+    let v0 = (b12 * b13 - b11 * b23) / denominator;
+    let lambda = b33 - (b13 * b13 + v0 * (b12 * b13 - b11 * b23)) / b11;
+    let alpha = (lambda / b11).sqrt();
+    let beta = (lambda * b11 / denominator).sqrt();
+    let u0 = -b13 * alpha * alpha / lambda;
+    let k = Matrix3::new(alpha, 0.0, u0, 0.0, beta, v0, 0.0, 0.0, 1.0);
+    (k.iter().all(|x| x.is_finite()) && alpha > 0.0 && beta > 0.0).then_some(k)
+}
+
+// Board pose from its homography and the camera matrix, snapped to the nearest rotation.
+fn board_pose(k: &Matrix3<f64>, h: &Matrix3<f64>) -> (Matrix3<f64>, Vector3<f64>) {
+    let k_inv = k.try_inverse().unwrap_or_else(Matrix3::identity);
+    let mut scale = 1.0 / (k_inv * h.column(0)).norm();
+    // The homography's sign is arbitrary, but the board has to be in front of the camera
+    if (k_inv * h.column(2)).z < 0.0 {
+        scale = -scale;
+    }
+    let r1 = k_inv * h.column(0) * scale;
+    let r2 = k_inv * h.column(1) * scale;
+    let t = k_inv * h.column(2) * scale;
+    let r = Matrix3::from_columns(&[r1, r2, r1.cross(&r2)]);
+    let svd = r.svd(true, true);
+    let rotation = match (svd.u, svd.v_t) {
+        (Some(u), Some(v_t)) => {
+            let mut d = Matrix3::identity();
+            d[(2, 2)] = (u * v_t).determinant().signum();
+            u * d * v_t
+        }
+        _ => Matrix3::identity(),
+    };
+    (rotation, t)
+}
+
+// Everything that's refined: the camera, and one board pose per frame.
+struct Model {
+    intrinsics: CameraIntrinsics,
+    poses: Vec<(Matrix3<f64>, Vector3<f64>)>,
+}
+
+impl Model {
+    fn camera_params(&self) -> Vec<f64> {
+        let i = &self.intrinsics;
+        let mut params = vec![i.fx, i.fy, i.cx, i.cy];
+        match i.distortion {
+            Distortion::None => {}
+            Distortion::BrownConrady { k1, k2, k3, p1, p2 } => params.extend([k1, k2, k3, p1, p2]),
+            Distortion::KannalaBrandt { k1, k2, k3, k4 } => params.extend([k1, k2, k3, k4]),
+        }
+        params
+    }
+
+    fn to_params(&self) -> DVector<f64> {
+        let mut params = self.camera_params();
+        for (rotation, translation) in &self.poses {
+            let w = Rotation3::from_matrix_unchecked(*rotation).scaled_axis();
+            params.extend([w.x, w.y, w.z, translation.x, translation.y, translation.z]);
+        }
+        DVector::from_vec(params)
+    }
+
+    // The inverse of `to_params`, using `self` only for the shape of the distortion.
+    fn with_params(&self, p: &DVector<f64>) -> Model {
+        let distortion = match self.intrinsics.distortion {
+            Distortion::None => Distortion::None,
+            Distortion::BrownConrady { .. } => Distortion::BrownConrady { k1: p[4], k2: p[5], k3: p[6], p1: p[7], p2: p[8] },
+            Distortion::KannalaBrandt { .. } => Distortion::KannalaBrandt { k1: p[4], k2: p[5], k3: p[6], k4: p[7] },
+        };
+        let camera = self.camera_params().len();
+        Model {
+            intrinsics: CameraIntrinsics::new(p[0], p[1], p[2], p[3]).with_distortion(distortion),
+            poses: (0..self.poses.len()).map(|i| {
+                let o = camera + 6 * i;
+                let rotation = Rotation3::new(Vector3::new(p[o], p[o + 1], p[o + 2])).into_inner();
+                (rotation, Vector3::new(p[o + 3], p[o + 4], p[o + 5]))
+            }).collect(),
+        }
+    }
+
+    fn cost(&self, observations: &[Observations]) -> f64 {
+        observations.iter().zip(&self.poses)
+            .map(|(obs, (rotation, translation))| squared_error(&self.intrinsics, rotation, translation, obs))
+            .sum()
+    }
+}
+
+// Joint Levenberg-Marquardt over the camera and every pose. The Jacobian is taken numerically, one
+// frame at a time, since each frame's residuals only depend on the camera and its own pose.
+fn refine(observations: &[Observations], initial: Model, max_iterations: u32) -> Model {
+    let mut model = initial;
+    let mut params = model.to_params();
+    let camera = model.camera_params().len();
+    let n = params.len();
+    let mut current = model.cost(observations);
+    let mut lambda = 1e-3;
+
+    for _ in 0..max_iterations {
+        let mut jtj = DMatrix::<f64>::zeros(n, n);
+        let mut jtr = DVector::<f64>::zeros(n);
+        for (frame, obs) in observations.iter().enumerate() {
+            let columns: Vec<usize> = (0..camera).chain(camera + 6 * frame..camera + 6 * frame + 6).collect();
+            let residuals = |p: &DVector<f64>| -> Option<Vec<f64>> {
+                let m = model.with_params(p);
+                let (rotation, translation) = &m.poses[frame];
+                let mut out = Vec::with_capacity(obs.object.len() * 2);
+                for (x, seen) in obs.object.iter().zip(&obs.image) {
+                    let p = reproject(&m.intrinsics, rotation, translation, x)?;
+                    out.extend([p[0] - seen[0], p[1] - seen[1]]);
+                }
+                Some(out)
+            };
+            let Some(r) = residuals(&params) else {
+                return model;
+            };
+            let mut j = DMatrix::<f64>::zeros(r.len(), columns.len());
+            for (c, &col) in columns.iter().enumerate() {
+                let step = 1e-6 * params[col].abs().max(1e-3);
+                let mut plus = params.clone();
+                let mut minus = params.clone();
+                plus[col] += step;
+                minus[col] -= step;
+                let (Some(a), Some(b)) = (residuals(&plus), residuals(&minus)) else {
+                    return model;
+                };
+                for row in 0..r.len() {
+                    j[(row, c)] = (a[row] - b[row]) / (2.0 * step);
+                }
+            }
+            let block = j.transpose() * &j;
+            let gradient = j.transpose() * DVector::from_vec(r);
+            for (a, &row) in columns.iter().enumerate() {
+                jtr[row] += gradient[a];
+                for (b, &col) in columns.iter().enumerate() {
+                    jtj[(row, col)] += block[(a, b)];
+                }
+            }
+        }
+
+        let mut improved = false;
+        while lambda < 1e10 {
+            let mut damped = jtj.clone();
+            for i in 0..n {
+                damped[(i, i)] += lambda * jtj[(i, i)].max(1e-12);
+            }
+            let Some(step) = damped.cholesky().map(|c| c.solve(&(-&jtr))) else {
+                lambda *= 10.0;
+                continue;
+            };
+            let next_params = &params + &step;
+            let next = model.with_params(&next_params);
+            let next_cost = next.cost(observations);
+            if next_cost <= current {
+                improved = current - next_cost > 1e-12 * current.max(1.0);
+                params = next_params;
+                model = next;
+                current = next_cost;
+                lambda = (lambda / 10.0).max(1e-12);
+                break;
+            }
+            lambda *= 10.0;
+        }
+        if !improved {
+            break;
+        }
+    }
+    model
+}
@@ -0,0 +1,171 @@
+use crate::board::{BoardPose, TagBoard};
+use crate::detector::{CameraIntrinsics, Detection, Point, Pose, TagDetection};
+use crate::distortion::Distortion;
+use crate::error::Error;
+use crate::field::{CameraPose, FieldLayout};
+use crate::frames::Frame;
+use crate::pnp;
+use nalgebra::{Matrix2, Matrix3, Matrix6, Vector3};
+
+/// A 6x6 pose covariance, row-major, ordered x, y, z, then rotation about x, y and z, like ROS's
+/// `PoseWithCovariance`. The rotation entries are for a small rotation about the axes of the frame
+/// the pose is expressed in, in radians.
+pub type Covariance = [[f64; 6]; 6];
+
+/// Standard deviation of the error on each corner coordinate, in pixels of the image the corners
+/// were detected in, before any undistortion. Errors are assumed to be independent between corners.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CornerNoise {
+    pub sigma_x: f64,
+    pub sigma_y: f64,
+}
+
+impl CornerNoise {
+    pub fn isotropic(sigma: f64) -> CornerNoise {
+        CornerNoise {
+            sigma_x: sigma,
+            sigma_y: sigma,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoseWithCovariance {
+    pub pose: Pose,
+    pub covariance: Covariance,
+}
+
+// Inverse of the Fisher information of the corners' projections at `pose`, in [t, w] order.
+fn covariance_at(object: &[Vector3<f64>], pose: &Pose, intrinsics: &CameraIntrinsics, noise: &CornerNoise) -> Result<Matrix6<f64>, Error> {
+    if !(noise.sigma_x > 0.0 && noise.sigma_y > 0.0) {
+        return Err(Error::InvalidConfig { field: "noise", reason: "standard deviations must be positive" });
+    }
+    let rotation = pose.rot.matrix();
+    let translation = Vector3::from(pose.pos);
+    let mut information = Matrix6::zeros();
+    for x in object {
+        if (rotation * x + translation).z <= f64::EPSILON {
+            return Err(Error::DegeneratePose("a corner is behind the camera"));
+        }
+        let (rows, _) = pnp::jacobian(&intrinsics.pinhole(), &rotation, &translation, x);
+        // The noise is on the corners as they were seen, so carry the pinhole rows through the
+        // distortion, scaled so that both sides are in pixels
+        let c = rotation * x + translation;
+        let d = distortion_jacobian(&intrinsics.distortion, [c.x / c.z, c.y / c.z]);
+        let (fx, fy) = (intrinsics.fx, intrinsics.fy);
+        let rows = [
+            rows[0] * d[(0, 0)] + rows[1] * (d[(0, 1)] * fx / fy),
+            rows[0] * (d[(1, 0)] * fy / fx) + rows[1] * d[(1, 1)],
+        ];
+        for (j, sigma) in rows.iter().zip([noise.sigma_x, noise.sigma_y]) {
+            information += j * j.transpose() / (sigma * sigma);
+        }
+    }
+    let covariance = information.try_inverse()
+        .ok_or(Error::DegeneratePose("corners don't constrain every degree of freedom"))?;
+
+    // The Jacobian's columns are rotation first, then translation
+    let mut out = Matrix6::zeros();
+    for i in 0..6 {
+        for j in 0..6 {
+            out[(i, j)] = covariance[((i + 3) % 6, (j + 3) % 6)];
+        }
+    }
+    Ok(out)
+}
+
+// Derivative of `distortion.distort` at `p` on the normalized image plane, by central differences.
+fn distortion_jacobian(distortion: &Distortion, p: Point) -> Matrix2<f64> {
+    if *distortion == Distortion::None {
+        return Matrix2::identity();
+    }
+    let step = 1e-7;
+    let mut out = Matrix2::zeros();
+    for i in 0..2 {
+        let (mut plus, mut minus) = (p, p);
+        plus[i] += step;
+        minus[i] -= step;
+        let (a, b) = (distortion.distort(plus), distortion.distort(minus));
+        out[(0, i)] = (a[0] - b[0]) / (2.0 * step);
+        out[(1, i)] = (a[1] - b[1]) / (2.0 * step);
+    }
+    out
+}
+
+fn to_array(m: &Matrix6<f64>) -> Covariance {
+    let mut out = [[0.0; 6]; 6];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, el) in row.iter_mut().enumerate() {
+            *el = m[(i, j)];
+        }
+    }
+    out
+}
+
+impl TagDetection {
+    /// Estimates the tag's pose and how uncertain it is, given the noise on its corners.
+    ///
+    /// The covariance is first-order: it's exact for small noise and gets optimistic as the noise
+    /// grows, or as the tag gets close to edge-on.
+    pub fn estimate_pose_with_covariance(&self, intrinsics: &CameraIntrinsics, tag_size: f64, noise: &CornerNoise) -> Result<PoseWithCovariance, Error> {
+        let pose = self.try_estimate_pose(intrinsics, tag_size)?;
+        let covariance = covariance_at(&pnp::tag_corners(tag_size), &pose, intrinsics, noise)?;
+        Ok(PoseWithCovariance {
+            pose,
+            covariance: to_array(&covariance),
+        })
+    }
+}
+
+impl Detection {
+    pub fn estimate_pose_with_covariance(&self, intrinsics: &CameraIntrinsics, tag_size: f64, noise: &CornerNoise) -> Result<PoseWithCovariance, Error> {
+        TagDetection::try_from(self)?.estimate_pose_with_covariance(intrinsics, tag_size, noise)
+    }
+}
+
+impl TagBoard {
+    /// Covariance of a board pose from `estimate_pose`, over the tags it was solved from.
+    pub fn pose_covariance(&self, estimate: &BoardPose, intrinsics: &CameraIntrinsics, noise: &CornerNoise) -> Result<Covariance, Error> {
+        let object: Vec<Vector3<f64>> = estimate.ids.iter()
+            .filter_map(|&id| self.corners(id))
+            .flat_map(|corners| corners.iter().map(|c| Vector3::new(c[0], c[1], c[2])))
+            .collect();
+        Ok(to_array(&covariance_at(&object, &estimate.pose, intrinsics, noise)?))
+    }
+}
+
+impl FieldLayout {
+    /// Covariance of a camera pose from `localize`, in the world frame.
+    pub fn pose_covariance(&self, camera: &CameraPose, intrinsics: &CameraIntrinsics, noise: &CornerNoise) -> Result<Covariance, Error> {
+        // Solve in the camera frame, where the Jacobian is simple, then carry the result over
+        let world_from_camera = camera.pose.convert_child(Frame::WpiLib, Frame::CameraCv);
+        let estimate = BoardPose {
+            pose: world_from_camera.inverse(),
+            error: camera.error,
+            ids: camera.ids.clone(),
+        };
+        let in_camera = self.board().pose_covariance(&estimate, intrinsics, noise)?;
+        let in_camera = Matrix6::from_fn(|i, j| in_camera[i][j]);
+
+        // Perturbing the camera-from-world pose by (dt, dw) perturbs its inverse by
+        // (-R dt - R [t]x dw, -R dw), where R is the world-from-camera rotation and t is the
+        // camera-from-world translation
+        let r = world_from_camera.rot.matrix();
+        let t = Vector3::from(estimate.pose.pos);
+        let mut jacobian = Matrix6::zeros();
+        jacobian.fixed_view_mut::<3, 3>(0, 0).copy_from(&-r);
+        jacobian.fixed_view_mut::<3, 3>(0, 3).copy_from(&(-r * cross_matrix(&t)));
+        jacobian.fixed_view_mut::<3, 3>(3, 3).copy_from(&-r);
+        Ok(to_array(&(jacobian * in_camera * jacobian.transpose())))
+    }
+}
+
+fn cross_matrix(v: &Vector3<f64>) -> Matrix3<f64> {
+    Matrix3::new(
+        0.0, -v.z, v.y,
+        v.z, 0.0, -v.x,
+        -v.y, v.x, 0.0,
+    )
+}
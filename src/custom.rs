@@ -0,0 +1,147 @@
+use crate::error::Error;
+use crate::family::{self, CustomFamilyData, TagFamily};
+
+/// Builds a tag family from a code table, for families that don't ship with libapriltag.
+///
+/// Bit positions are in cells relative to the top-left corner of the black border, the same as
+/// `bit_x`/`bit_y` in `apriltag_family_t`, and may be negative for layouts with data outside the
+/// border. The bits must be ordered the way libapriltag expects: each quarter of them is the
+/// previous quarter rotated clockwise by 90 degrees, with the center bit (if any) last. `build`
+/// rejects layouts that aren't.
+///
+/// `build` registers the family for the rest of the program and returns a `TagFamily` that can be
+/// passed to `Detector::add` like any built-in one.
+#[derive(Clone, Debug)]
+pub struct CustomFamily {
+    name: String,
+    codes: Vec<u64>,
+    bits: Vec<(i32, i32)>,
+    width_at_border: u32,
+    total_width: u32,
+    reversed_border: bool,
+    min_hamming: Option<u32>,
+}
+
+impl CustomFamily {
+    pub fn new(name: impl Into<String>) -> CustomFamily {
+        CustomFamily {
+            name: name.into(),
+            codes: Vec::new(),
+            bits: Vec::new(),
+            width_at_border: 0,
+            total_width: 0,
+            reversed_border: false,
+            min_hamming: None,
+        }
+    }
+
+    pub fn codes(mut self, codes: impl Into<Vec<u64>>) -> CustomFamily {
+        self.codes = codes.into();
+        self
+    }
+
+    pub fn bit_layout(mut self, bit_x: &[i32], bit_y: &[i32]) -> CustomFamily {
+        self.bits = bit_x.iter().copied().zip(bit_y.iter().copied()).collect();
+        // Keep a length mismatch visible to build()
+        if bit_x.len() != bit_y.len() {
+            self.bits.clear();
+        }
+        self
+    }
+
+    pub fn width_at_border(mut self, width: u32) -> CustomFamily {
+        self.width_at_border = width;
+        self
+    }
+
+    pub fn total_width(mut self, width: u32) -> CustomFamily {
+        self.total_width = width;
+        self
+    }
+
+    pub fn reversed_border(mut self, reversed: bool) -> CustomFamily {
+        self.reversed_border = reversed;
+        self
+    }
+
+    /// Records the family's minimum Hamming distance instead of computing it in `build`, which
+    /// takes time quadratic in the number of codes.
+    pub fn min_hamming(mut self, distance: u32) -> CustomFamily {
+        self.min_hamming = Some(distance);
+        self
+    }
+
+    pub fn build(self) -> Result<TagFamily, Error> {
+        let nbits = self.bits.len() as u32;
+        if self.name.is_empty() {
+            return Err(Error::InvalidConfig { field: "name", reason: "must not be empty" });
+        }
+        if nbits == 0 || nbits > 64 {
+            return Err(Error::InvalidConfig { field: "bit_layout", reason: "must have between 1 and 64 bits, with bit_x and bit_y the same length" });
+        }
+        if nbits % 4 > 1 {
+            return Err(Error::InvalidConfig { field: "bit_layout", reason: "bit count must be a multiple of 4, or one more than a multiple of 4" });
+        }
+        if self.codes.is_empty() || self.codes.len() > u32::MAX as usize {
+            return Err(Error::InvalidConfig { field: "codes", reason: "must have at least one code" });
+        }
+        if nbits < 64 && self.codes.iter().any(|&code| code >> nbits != 0) {
+            return Err(Error::InvalidConfig { field: "codes", reason: "codes must fit in the number of bits in the layout" });
+        }
+        if self.width_at_border < 2 || self.width_at_border > i32::MAX as u32 {
+            return Err(Error::InvalidConfig { field: "width_at_border", reason: "must be at least 2" });
+        }
+        if self.total_width < self.width_at_border || self.total_width > i32::MAX as u32 {
+            return Err(Error::InvalidConfig { field: "total_width", reason: "must be at least width_at_border" });
+        }
+        if !(self.total_width - self.width_at_border).is_multiple_of(2) {
+            return Err(Error::InvalidConfig { field: "total_width", reason: "must leave the same margin on each side of the border" });
+        }
+
+        let total = self.total_width as i32;
+        let border_start = (total - self.width_at_border as i32) / 2;
+        let inside = |v: i32| (0..total).contains(&(v + border_start));
+        if !self.bits.iter().all(|&(x, y)| inside(x) && inside(y)) {
+            return Err(Error::InvalidConfig { field: "bit_layout", reason: "bits must lie inside total_width" });
+        }
+        if !family::is_clockwise(&self.bits, self.width_at_border) {
+            return Err(Error::InvalidConfig { field: "bit_layout", reason: family::CLOCKWISE_LAYOUT });
+        }
+
+        let min_hamming = match self.min_hamming {
+            Some(distance) => distance,
+            None => min_distance(&self.codes, nbits),
+        };
+
+        family::register_custom(CustomFamilyData {
+            name: self.name,
+            codes: self.codes,
+            // Negative offsets wrap around, just like in the generated C families
+            bit_x: self.bits.iter().map(|&(x, _)| x as u32).collect(),
+            bit_y: self.bits.iter().map(|&(_, y)| y as u32).collect(),
+            width_at_border: self.width_at_border as i32,
+            total_width: self.total_width as i32,
+            reversed_border: self.reversed_border,
+            min_hamming: min_hamming.min(i32::MAX as u32) as i32,
+        })
+    }
+}
+
+// Smallest distance between any code and any rotation of another code (or a non-trivial rotation
+// of itself).
+pub(crate) fn min_distance(codes: &[u64], nbits: u32) -> u32 {
+    let mut best = nbits;
+    for (i, &a) in codes.iter().enumerate() {
+        let mut rotated = a;
+        for rotation in 0..4 {
+            if rotation > 0 {
+                best = best.min(family::hamming_distance(a, rotated));
+            }
+            for &b in &codes[i + 1..] {
+                best = best.min(family::hamming_distance(rotated, b));
+            }
+            rotated = family::rotate90(rotated, nbits);
+        }
+    }
+    best
+}
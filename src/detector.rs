@@ -2,11 +2,13 @@ use crate::native::*;
 use crate::family::TagFamily;
 // use crate::array::Array;
 use crate::image::ImageU8;
+use crate::error::Error;
+use crate::homography;
 use std::mem::MaybeUninit;
 #[cfg(feature = "3d")]
-use nalgebra::Matrix3;
+use nalgebra::{Isometry3, Matrix3, Quaternion, Rotation3, UnitQuaternion, Vector3};
 #[cfg(feature = "3d")]
-use nalgebra::linalg::{QR};
+use crate::distortion::Distortion;
 
 // These are here as a result of libapriltag using `static inline` on all of its useful zarray
 // functions. That's great for efficiency (kind of), but not so much for porting.
@@ -18,55 +20,83 @@ extern "C" {
 
 
 #[cfg(feature = "3d")]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(dead_code)]
 pub struct CameraIntrinsics {
     pub fx: f64,
     pub fy: f64,
     pub cx: f64,
     pub cy: f64,
+    /// Corners are undistorted with this before any pose is estimated. Struct literals have to
+    /// name it, so use `Distortion::None` (or `new`) for an ideal pinhole camera.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub distortion: Distortion,
 }
 
 #[cfg(feature = "3d")]
 #[allow(dead_code)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RotationData"))]
 pub struct Rotation {
     quat: [f64; 4],
 }
 
+// The serialized form of a Rotation, which has to be checked before it's trusted as a unit
+// quaternion.
+#[cfg(all(feature = "3d", feature = "serde"))]
+#[derive(serde::Deserialize)]
+struct RotationData {
+    quat: [f64; 4],
+}
+
+#[cfg(all(feature = "3d", feature = "serde"))]
+impl TryFrom<RotationData> for Rotation {
+    type Error = Error;
+
+    fn try_from(data: RotationData) -> Result<Rotation, Error> {
+        let [w, x, y, z] = data.quat;
+        Rotation::from_quaternion(w, x, y, z)
+    }
+}
+
 #[cfg(feature = "3d")]
 #[allow(dead_code)]
 impl Rotation {
-    unsafe fn from_matd(mat: *mut matd_t) -> Rotation {
+    unsafe fn from_matd(mat: *mut matd_t) -> Result<Rotation, Error> {
+        if mat.is_null() {
+            return Err(Error::DegeneratePose("no rotation was produced"));
+        }
         let m = Matrix3::from_fn(|i, j| {
             matd_get(mat, i as u32, j as u32)
         });
         matd_destroy(mat);
+        Rotation::from_matrix(m)
+    }
 
-        //orthogonalize (source: WPILib source https://github.com/wpilibsuite/allwpilib/blob/main/apriltag/src/main/native/cpp/AprilTagPoseEstimator.cpp#L24)
-        let qr = QR::new(m);
-        let mut q = qr.q();
-        let r = qr.r();
-
-        for i in 0..3 {
-            if r[(i, i)] < 0.0 {
-                for j in 0..3 {
-                    q[(j, i)] = -q[(j, i)];
-                }
-            }
+    /// Accepts any matrix within a small tolerance of a rotation (‖MᵀM − I‖ ≤ 1e-6), snapping it
+    /// to the closest rotation first. Sheared, scaled and mirrored matrices are rejected.
+    pub fn from_matrix(m: Matrix3<f64>) -> Result<Rotation, Error> {
+        if !m.iter().all(|v| v.is_finite()) {
+            return Err(Error::DegeneratePose("rotation matrix has non-finite entries"));
         }
-
-        let m = q;
-
-        // translate to quaternion (source: WPILib's wpimath/algorithms.md)
-        if (m * m.transpose() - Matrix3::identity()).norm() > 1e-9 {
-            panic!("rotation matrix isn't orthogonal");
+        if (m.transpose() * m - Matrix3::identity()).norm() > 1e-6 {
+            return Err(Error::DegeneratePose("rotation matrix isn't orthogonal"));
         }
-
-        if (m.determinant() - 1.0).abs() > 1e-9 {
-            panic!("rotation matrix is orthogonal, but not special orthogonal")
+        if m.determinant() <= 0.0 {
+            return Err(Error::DegeneratePose("rotation matrix is orthogonal, but not special orthogonal"));
         }
 
+        // The orthogonal factor of the polar decomposition is the closest rotation in the
+        // Frobenius norm
+        let svd = m.svd(true, true);
+        let m = match (svd.u, svd.v_t) {
+            (Some(u), Some(v_t)) => u * v_t,
+            _ => return Err(Error::DegeneratePose("rotation matrix has no singular value decomposition")),
+        };
+
+        // translate to quaternion (source: WPILib's wpimath/algorithms.md)
         let trace = m.trace();
         let m00 = m[(0,0)];
         let m11 = m[(1,1)];
@@ -77,7 +107,7 @@ impl Rotation {
             let x = (m[(2,1)] - m[(1,2)]) * s;
             let y = (m[(0,2)] - m[(2,0)]) * s;
             let z = (m[(1,0)] - m[(0,1)]) * s;
-            Rotation{quat: [w,x,y,z]}
+            Ok(Rotation{quat: [w,x,y,z]})
         } else {
             if m00 > m11 && m00 > m22 {
                 let s = 2.0 * (1.0 + m00 - m11 - m22).sqrt();
@@ -85,25 +115,141 @@ impl Rotation {
                 let x = 0.25 * s;
                 let y = (m[(0,1)] + m[(1,0)]) / s;
                 let z = (m[(0,2)] + m[(2,0)]) / s;
-                Rotation{quat: [w,x,y,z]}
+                Ok(Rotation{quat: [w,x,y,z]})
             } else if m11 > m22 {
-                let s = 2.0 * (1.0 + m11 - m00 - m22);
+                let s = 2.0 * (1.0 + m11 - m00 - m22).sqrt();
                 let w = (m[(0,2)] - m[(2,0)]) / s;
                 let x = (m[(0,1)] + m[(1,0)]) / s;
                 let y = 0.25 * s;
                 let z = (m[(1,2)] + m[(2,1)]) / s;
-                Rotation{quat: [w,x,y,z]}
+                Ok(Rotation{quat: [w,x,y,z]})
             } else {
                 let s = 2.0 * (1.0 + m22 - m00 - m11).sqrt();
                 let w = (m[(1, 0)] - m[(0, 1)]) / s;
                 let x = (m[(0, 2)] + m[(2, 0)]) / s;
                 let y = (m[(1, 2)] + m[(2, 1)]) / s;
                 let z = 0.25 * s;
-                Rotation{quat: [w,x,y,z]}
+                Ok(Rotation{quat: [w,x,y,z]})
             }
         }
     }
 
+    pub fn identity() -> Rotation {
+        Rotation{quat: [1.0, 0.0, 0.0, 0.0]}
+    }
+
+    /// Any non-zero quaternion is accepted, and normalized.
+    pub fn from_quaternion(w: f64, x: f64, y: f64, z: f64) -> Result<Rotation, Error> {
+        let norm = (w * w + x * x + y * y + z * z).sqrt();
+        if !norm.is_normal() {
+            return Err(Error::DegeneratePose("quaternion has zero or non-finite length"));
+        }
+        Ok(Rotation::normalized(w, x, y, z))
+    }
+
+    fn normalized(w: f64, x: f64, y: f64, z: f64) -> Rotation {
+        let norm = (w * w + x * x + y * y + z * z).sqrt();
+        Rotation{quat: [w / norm, x / norm, y / norm, z / norm]}
+    }
+
+    /// Rotation by `angle` radians about `axis`, which doesn't need to be normalized.
+    pub fn from_axis_angle(axis: [f64; 3], angle: f64) -> Result<Rotation, Error> {
+        let norm = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt();
+        if !norm.is_normal() || !angle.is_finite() {
+            return Err(Error::DegeneratePose("axis has zero or non-finite length"));
+        }
+        let (s, c) = (angle / 2.0).sin_cos();
+        Ok(Rotation::normalized(c, s * axis[0] / norm, s * axis[1] / norm, s * axis[2] / norm))
+    }
+
+    /// The inverse of `roll`, `pitch` and `yaw`: yaw about z, then pitch about y, then roll about x.
+    pub fn from_roll_pitch_yaw(roll: f64, pitch: f64, yaw: f64) -> Rotation {
+        let (sr, cr) = (roll / 2.0).sin_cos();
+        let (sp, cp) = (pitch / 2.0).sin_cos();
+        let (sy, cy) = (yaw / 2.0).sin_cos();
+        Rotation::normalized(
+            cr * cp * cy + sr * sp * sy,
+            sr * cp * cy - cr * sp * sy,
+            cr * sp * cy + sr * cp * sy,
+            cr * cp * sy - sr * sp * cy,
+        )
+    }
+
+    /// The unit quaternion as `[w, x, y, z]`, with `w` never negative.
+    pub fn quaternion(&self) -> [f64; 4] {
+        if self.quat[0] < 0.0 {
+            self.quat.map(|v| -v)
+        } else {
+            self.quat
+        }
+    }
+
+    /// Unit axis and angle in radians, from 0 to pi. The axis is arbitrary for the identity.
+    pub fn axis_angle(&self) -> ([f64; 3], f64) {
+        let [w, x, y, z] = self.quaternion();
+        let s = (x * x + y * y + z * z).sqrt();
+        if s < f64::EPSILON {
+            return ([1.0, 0.0, 0.0], 0.0);
+        }
+        ([x / s, y / s, z / s], 2.0 * s.atan2(w))
+    }
+
+    /// `self` applied after `other`. Also available as `self * other`.
+    pub fn compose(&self, other: &Rotation) -> Rotation {
+        let [w1, x1, y1, z1] = self.quat;
+        let [w2, x2, y2, z2] = other.quat;
+        Rotation::normalized(
+            w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
+            w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
+            w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
+            w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
+        )
+    }
+
+    pub fn inverse(&self) -> Rotation {
+        let [w, x, y, z] = self.quat;
+        Rotation{quat: [w, -x, -y, -z]}
+    }
+
+    pub fn rotate(&self, v: [f64; 3]) -> [f64; 3] {
+        let r = self.matrix() * Vector3::new(v[0], v[1], v[2]);
+        [r.x, r.y, r.z]
+    }
+
+    /// Spherical linear interpolation along the shorter arc, from `self` at `t = 0` to `other` at
+    /// `t = 1`.
+    pub fn slerp(&self, other: &Rotation, t: f64) -> Rotation {
+        let a = self.quat;
+        let mut b = other.quat;
+        let mut dot: f64 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+        if dot < 0.0 {
+            b = b.map(|v| -v);
+            dot = -dot;
+        }
+        // Nearly parallel: the sine below vanishes, and a straight line is just as good
+        let (wa, wb) = if dot > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = dot.acos();
+            (((1.0 - t) * theta).sin() / theta.sin(), (t * theta).sin() / theta.sin())
+        };
+        Rotation::normalized(
+            wa * a[0] + wb * b[0],
+            wa * a[1] + wb * b[1],
+            wa * a[2] + wb * b[2],
+            wa * a[3] + wb * b[3],
+        )
+    }
+
+    pub fn matrix(&self) -> Matrix3<f64> {
+        let [w, x, y, z] = self.quat;
+        Matrix3::new(
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y),
+            2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x),
+            2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y),
+        )
+    }
+
     pub fn roll(&self) -> f64 {
         let w = self.quat[0];
         let x = self.quat[1];
@@ -163,7 +309,8 @@ impl Rotation {
 
 #[cfg(feature = "3d")]
 #[allow(dead_code)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Translation {
     pub x: f64,
     pub y: f64, 
@@ -173,24 +320,189 @@ pub struct Translation {
 #[cfg(feature = "3d")]
 #[allow(dead_code)]
 impl Translation {
-    unsafe fn from_matd(mat: *mut matd_t) -> Translation {
+    unsafe fn from_matd(mat: *mut matd_t) -> Result<Translation, Error> {
+        if mat.is_null() {
+            return Err(Error::DegeneratePose("no translation was produced"));
+        }
         let x = matd_get(mat, 0, 0);
         let y = matd_get(mat, 1, 0);
         let z = matd_get(mat, 2, 0);
         matd_destroy(mat);
-        Translation{x, y, z}
+        if !(x.is_finite() && y.is_finite() && z.is_finite()) {
+            return Err(Error::DegeneratePose("translation has non-finite entries"));
+        }
+        Ok(Translation{x, y, z})
     }
 }
 
 #[cfg(feature = "3d")]
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pose {
     pub rot: Rotation,
     pub pos: Translation,
 }
 
+#[cfg(feature = "3d")]
+impl Pose {
+    // Takes ownership of both matrices in `pose`. Translation goes first, so that both are freed
+    // even if the rotation turns out to be bad.
+    unsafe fn from_raw(pose: apriltag_pose_t) -> Result<Pose, Error> {
+        let pos = Translation::from_matd(pose.t);
+        let rot = Rotation::from_matd(pose.R);
+        Ok(Pose {
+            rot: rot?,
+            pos: pos?,
+        })
+    }
+
+    pub fn identity() -> Pose {
+        Pose {
+            rot: Rotation::identity(),
+            pos: Translation{x: 0.0, y: 0.0, z: 0.0},
+        }
+    }
+
+    /// Maps a point from this pose's frame into its parent frame.
+    pub fn transform_point(&self, point: [f64; 3]) -> [f64; 3] {
+        let [x, y, z] = self.rot.rotate(point);
+        [x + self.pos.x, y + self.pos.y, z + self.pos.z]
+    }
+
+    /// If `self` is the pose of B in A and `other` is the pose of C in B, this is the pose of C in A.
+    /// Also available as `self * other`.
+    pub fn compose(&self, other: &Pose) -> Pose {
+        let [x, y, z] = self.transform_point([other.pos.x, other.pos.y, other.pos.z]);
+        Pose {
+            rot: self.rot.compose(&other.rot),
+            pos: Translation{x, y, z},
+        }
+    }
+
+    /// The pose of the parent frame in this one.
+    pub fn inverse(&self) -> Pose {
+        let rot = self.rot.inverse();
+        let [x, y, z] = rot.rotate([self.pos.x, self.pos.y, self.pos.z]);
+        Pose {
+            rot,
+            pos: Translation{x: -x, y: -y, z: -z},
+        }
+    }
+}
+
+#[cfg(feature = "3d")]
+impl std::ops::Mul for Pose {
+    type Output = Pose;
+
+    fn mul(self, rhs: Pose) -> Pose {
+        self.compose(&rhs)
+    }
+}
+
+#[cfg(feature = "3d")]
+impl std::ops::Mul for Rotation {
+    type Output = Rotation;
+
+    fn mul(self, rhs: Rotation) -> Rotation {
+        self.compose(&rhs)
+    }
+}
+
+#[cfg(feature = "3d")]
+impl From<UnitQuaternion<f64>> for Rotation {
+    fn from(q: UnitQuaternion<f64>) -> Rotation {
+        Rotation::normalized(q.w, q.i, q.j, q.k)
+    }
+}
+
+#[cfg(feature = "3d")]
+impl From<Rotation> for UnitQuaternion<f64> {
+    fn from(r: Rotation) -> UnitQuaternion<f64> {
+        let [w, x, y, z] = r.quat;
+        UnitQuaternion::new_unchecked(Quaternion::new(w, x, y, z))
+    }
+}
+
+#[cfg(feature = "3d")]
+impl From<Rotation3<f64>> for Rotation {
+    fn from(r: Rotation3<f64>) -> Rotation {
+        UnitQuaternion::from_rotation_matrix(&r).into()
+    }
+}
+
+#[cfg(feature = "3d")]
+impl From<Rotation> for Rotation3<f64> {
+    fn from(r: Rotation) -> Rotation3<f64> {
+        UnitQuaternion::from(r).to_rotation_matrix()
+    }
+}
+
+#[cfg(feature = "3d")]
+impl From<Vector3<f64>> for Translation {
+    fn from(v: Vector3<f64>) -> Translation {
+        Translation{x: v.x, y: v.y, z: v.z}
+    }
+}
+
+#[cfg(feature = "3d")]
+impl From<Translation> for Vector3<f64> {
+    fn from(t: Translation) -> Vector3<f64> {
+        Vector3::new(t.x, t.y, t.z)
+    }
+}
+
+#[cfg(feature = "3d")]
+impl From<Isometry3<f64>> for Pose {
+    fn from(iso: Isometry3<f64>) -> Pose {
+        Pose {
+            rot: iso.rotation.into(),
+            pos: iso.translation.vector.into(),
+        }
+    }
+}
+
+#[cfg(feature = "3d")]
+impl From<Pose> for Isometry3<f64> {
+    fn from(pose: Pose) -> Isometry3<f64> {
+        Isometry3::from_parts(Vector3::from(pose.pos).into(), pose.rot.into())
+    }
+}
+
+/// Both solutions found by orthogonal iteration. A square tag seen from far away often fits two
+/// poses almost equally well (one of them "flipped"), so `alternate` is kept for callers that want
+/// to judge how trustworthy `best` is.
+#[cfg(feature = "3d")]
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoseEstimate {
+    pub best: Pose,
+    pub best_error: f64,
+    pub alternate: Option<(Pose, f64)>,
+}
+
+#[cfg(feature = "3d")]
+impl PoseEstimate {
+    /// Ratio of the two object-space errors, from 0 (unambiguous) to 1 (both poses fit equally
+    /// well). This is 0 when there is no second solution.
+    pub fn ambiguity(&self) -> f64 {
+        match self.alternate {
+            Some((_, alternate_error)) if alternate_error > 0.0 => {
+                self.best_error.min(alternate_error) / self.best_error.max(alternate_error)
+            }
+            Some(_) => 1.0,
+            None => 0.0,
+        }
+    }
+}
+
 pub type Point = [f64; 2];
 
+/// Row-major 3x3 homography from tag coordinates to image pixels. Tag coordinates run from -1 to
+/// 1 across the tag's black border, with `(0, 0)` at its center.
+pub type Homography = [[f64; 3]; 3];
+
 #[allow(dead_code)]
 pub struct Detection {
     raw: *mut apriltag_detection_t,
@@ -206,12 +518,33 @@ impl Drop for Detection {
 
 #[allow(dead_code)]
 impl Detection {
+    /// # Safety
+    /// `ptr` must be a detection allocated by libapriltag. The returned value takes ownership of it.
     pub unsafe fn from_raw(ptr: *mut apriltag_detection_t) -> Detection {
         Detection {
             raw: ptr
         }
     }
 
+    pub fn family(&self) -> TagFamily {
+        self.try_family().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Fails if the detection's family wasn't created through this crate.
+    pub fn try_family(&self) -> Result<TagFamily, Error> {
+        unsafe {
+            let fam = (*self.raw).family;
+            TagFamily::from_raw(fam).ok_or_else(|| {
+                let name = if fam.is_null() || (*fam).name.is_null() {
+                    String::new()
+                } else {
+                    std::ffi::CStr::from_ptr((*fam).name).to_string_lossy().into_owned()
+                };
+                Error::UnknownFamily(name)
+            })
+        }
+    }
+
     pub fn id(&self) -> u32 {
         unsafe {(*self.raw).id as u32}
     }
@@ -232,33 +565,356 @@ impl Detection {
         unsafe {(*self.raw).p}
     }
 
+    pub fn homography(&self) -> Homography {
+        let mut h = [[0.0; 3]; 3];
+        unsafe {
+            let mat = (*self.raw).H;
+            for (i, row) in h.iter_mut().enumerate() {
+                for (j, el) in row.iter_mut().enumerate() {
+                    *el = matd_get(mat, i as u32, j as u32);
+                }
+            }
+        }
+        h
+    }
+
+    #[cfg(feature = "3d")]
+    pub fn homography_matrix(&self) -> Matrix3<f64> {
+        let h = self.homography();
+        Matrix3::from_fn(|i, j| h[i][j])
+    }
+
+    /// Maps a point in tag coordinates to image pixels.
+    pub fn project(&self, tag_point: Point) -> Option<Point> {
+        homography::project(&self.homography(), tag_point)
+    }
+
+    /// Maps an image pixel onto the tag plane, in tag coordinates.
+    pub fn unproject(&self, image_point: Point) -> Option<Point> {
+        let inverse = homography::invert(&self.homography())?;
+        homography::project(&inverse, image_point)
+    }
+
+    // Shifts the detection by (dx, dy) pixels. Used to move detections made in an image view back
+    // into the coordinates of the full frame.
+    fn translate(&mut self, dx: f64, dy: f64) {
+        unsafe {
+            let det = &mut *self.raw;
+            det.c[0] += dx;
+            det.c[1] += dy;
+            for p in det.p.iter_mut() {
+                p[0] += dx;
+                p[1] += dy;
+            }
+            // H' = T * H, where T is the translation by (dx, dy)
+            for j in 0..3 {
+                let w = matd_get(det.H, 2, j);
+                matd_put(det.H, 0, j, matd_get(det.H, 0, j) + dx * w);
+                matd_put(det.H, 1, j, matd_get(det.H, 1, j) + dy * w);
+            }
+        }
+    }
+
     #[cfg(feature = "3d")]
     pub fn estimate_pose(&self, intrinsics: &CameraIntrinsics, tag_size: f64) -> Pose {
+        self.try_estimate_pose(intrinsics, tag_size).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[cfg(feature = "3d")]
+    pub fn try_estimate_pose(&self, intrinsics: &CameraIntrinsics, tag_size: f64) -> Result<Pose, Error> {
+        if intrinsics.distortion != Distortion::None {
+            return TagDetection::try_from(self)?.try_estimate_pose(intrinsics, tag_size);
+        }
+        unsafe {estimate_pose_raw(self.raw, intrinsics, tag_size)}
+    }
+
+    /// Runs at most `iterations` rounds of orthogonal iteration and returns both candidate poses.
+    #[cfg(feature = "3d")]
+    pub fn estimate_pose_candidates(&self, intrinsics: &CameraIntrinsics, tag_size: f64, iterations: u32) -> Result<PoseEstimate, Error> {
+        if intrinsics.distortion != Distortion::None {
+            return TagDetection::try_from(self)?.estimate_pose_candidates(intrinsics, tag_size, iterations);
+        }
+        unsafe {estimate_pose_candidates_raw(self.raw, intrinsics, tag_size, iterations)}
+    }
+}
+
+#[cfg(feature = "3d")]
+fn detection_info(det: *mut apriltag_detection_t, intrinsics: &CameraIntrinsics, tag_size: f64) -> Result<apriltag_detection_info_t, Error> {
+    if !(tag_size.is_finite() && tag_size > 0.0) {
+        return Err(Error::InvalidConfig { field: "tag_size", reason: "must be positive" });
+    }
+    Ok(apriltag_detection_info_t {
+        det,
+        tagsize: tag_size,
+        fx: intrinsics.fx,
+        fy: intrinsics.fy,
+        cx: intrinsics.cx,
+        cy: intrinsics.cy,
+    })
+}
+
+#[cfg(feature = "3d")]
+unsafe fn estimate_pose_raw(det: *mut apriltag_detection_t, intrinsics: &CameraIntrinsics, tag_size: f64) -> Result<Pose, Error> {
+    let mut info = detection_info(det, intrinsics, tag_size)?;
+    let info_ptr = &mut info as *mut apriltag_detection_info_t;
+
+    let mut pose = MaybeUninit::<apriltag_pose_t>::uninit();
+
+    estimate_tag_pose(info_ptr, pose.as_mut_ptr());
+    Pose::from_raw(pose.assume_init())
+}
+
+#[cfg(feature = "3d")]
+unsafe fn estimate_pose_candidates_raw(det: *mut apriltag_detection_t, intrinsics: &CameraIntrinsics, tag_size: f64, iterations: u32) -> Result<PoseEstimate, Error> {
+    if iterations == 0 {
+        return Err(Error::InvalidConfig { field: "iterations", reason: "must be at least 1" });
+    }
+    if iterations > i32::MAX as u32 {
+        return Err(Error::InvalidConfig { field: "iterations", reason: "too large" });
+    }
+    let mut info = detection_info(det, intrinsics, tag_size)?;
+    let info_ptr = &mut info as *mut apriltag_detection_info_t;
+
+    let mut err1 = 0.0;
+    let mut err2 = 0.0;
+    let mut pose1 = apriltag_pose_t { R: std::ptr::null_mut(), t: std::ptr::null_mut() };
+    let mut pose2 = apriltag_pose_t { R: std::ptr::null_mut(), t: std::ptr::null_mut() };
+
+    estimate_tag_pose_orthogonal_iteration(info_ptr, &mut err1, &mut pose1, &mut err2, &mut pose2, iterations as i32, 1e-7);
+
+    // libapriltag leaves the second pose empty when it only finds one local minimum
+    let alternate = if pose2.R.is_null() && pose2.t.is_null() {
+        None
+    } else {
+        Pose::from_raw(pose2).ok().map(|pose| (pose, err2))
+    };
+    let best = Pose::from_raw(pose1)?;
+
+    let mut estimate = PoseEstimate {
+        best,
+        best_error: err1,
+        alternate,
+    };
+    if let Some((pose, error)) = estimate.alternate {
+        if error < estimate.best_error {
+            estimate.alternate = Some((estimate.best, estimate.best_error));
+            estimate.best = pose;
+            estimate.best_error = error;
+        }
+    }
+    Ok(estimate)
+}
+
+/// A detection copied out of libapriltag. Unlike `Detection`, this is plain data: it can be cloned,
+/// sent between threads and kept around after the detector is gone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TagDetection {
+    pub family: TagFamily,
+    pub id: u32,
+    pub hamming: u32,
+    pub decision_margin: f32,
+    pub center: Point,
+    pub corners: [Point; 4],
+    pub homography: Homography,
+}
+
+impl TryFrom<&Detection> for TagDetection {
+    type Error = Error;
+
+    fn try_from(det: &Detection) -> Result<TagDetection, Error> {
+        Ok(TagDetection {
+            family: det.try_family()?,
+            id: det.id(),
+            hamming: det.hamming(),
+            decision_margin: det.decision_margin(),
+            center: det.center(),
+            corners: det.corners(),
+            homography: det.homography(),
+        })
+    }
+}
+
+// A temporary apriltag_detection_t built from a TagDetection, for the C pose estimators.
+#[cfg(feature = "3d")]
+struct RawDetection {
+    det: apriltag_detection_t,
+}
+
+#[cfg(feature = "3d")]
+impl RawDetection {
+    fn new(det: &TagDetection) -> Result<RawDetection, Error> {
         unsafe {
-            let mut info = apriltag_detection_info_t {
-                det: self.raw,
-                tagsize: tag_size,
-                fx: intrinsics.fx,
-                fy: intrinsics.fy,
-                cx: intrinsics.cx,
-                cy: intrinsics.cy,
-            };
-            let info_ptr = &mut info as *mut apriltag_detection_info_t;
-
-            let mut pose = MaybeUninit::<apriltag_pose_t>::uninit();
-
-            estimate_tag_pose(info_ptr, pose.as_mut_ptr());
-            let pose = pose.assume_init();
-
-            Pose {
-                rot: Rotation::from_matd(pose.R),
-                pos: Translation::from_matd(pose.t),
+            let h = matd_create(3, 3);
+            if h.is_null() {
+                return Err(Error::AllocationFailed("homography"));
+            }
+            for (i, row) in det.homography.iter().enumerate() {
+                for (j, el) in row.iter().enumerate() {
+                    matd_put(h, i as u32, j as u32, *el);
+                }
             }
+            Ok(RawDetection {
+                det: apriltag_detection_t {
+                    family: det.family.family().into_raw(),
+                    id: det.id as i32,
+                    hamming: det.hamming as i32,
+                    decision_margin: det.decision_margin,
+                    H: h,
+                    c: det.center,
+                    p: det.corners,
+                },
+            })
+        }
+    }
+}
+
+#[cfg(feature = "3d")]
+impl Drop for RawDetection {
+    fn drop(&mut self) {
+        unsafe {
+            matd_destroy(self.det.H);
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl TagDetection {
+    #[cfg(feature = "3d")]
+    pub fn homography_matrix(&self) -> Matrix3<f64> {
+        Matrix3::from_fn(|i, j| self.homography[i][j])
+    }
+
+    /// Maps a point in tag coordinates to image pixels.
+    pub fn project(&self, tag_point: Point) -> Option<Point> {
+        homography::project(&self.homography, tag_point)
+    }
+
+    /// Maps an image pixel onto the tag plane, in tag coordinates.
+    pub fn unproject(&self, image_point: Point) -> Option<Point> {
+        let inverse = homography::invert(&self.homography)?;
+        homography::project(&inverse, image_point)
+    }
+
+    #[cfg(feature = "3d")]
+    pub fn estimate_pose(&self, intrinsics: &CameraIntrinsics, tag_size: f64) -> Pose {
+        self.try_estimate_pose(intrinsics, tag_size).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[cfg(feature = "3d")]
+    pub fn try_estimate_pose(&self, intrinsics: &CameraIntrinsics, tag_size: f64) -> Result<Pose, Error> {
+        let mut raw = RawDetection::new(&self.undistorted(intrinsics)?)?;
+        unsafe {estimate_pose_raw(&mut raw.det, intrinsics, tag_size)}
+    }
+
+    /// Runs at most `iterations` rounds of orthogonal iteration and returns both candidate poses.
+    #[cfg(feature = "3d")]
+    pub fn estimate_pose_candidates(&self, intrinsics: &CameraIntrinsics, tag_size: f64, iterations: u32) -> Result<PoseEstimate, Error> {
+        let mut raw = RawDetection::new(&self.undistorted(intrinsics)?)?;
+        unsafe {estimate_pose_candidates_raw(&mut raw.det, intrinsics, tag_size, iterations)}
+    }
+
+    /// The detection as an ideal pinhole camera would have seen it: corners and center are
+    /// undistorted, and the homography is refit to the new corners.
+    #[cfg(feature = "3d")]
+    pub fn undistorted(&self, intrinsics: &CameraIntrinsics) -> Result<TagDetection, Error> {
+        if intrinsics.distortion == Distortion::None {
+            return Ok(*self);
         }
+        let undistort = |p| intrinsics.undistort_point(p)
+            .ok_or(Error::DegeneratePose("a corner is outside what the distortion model can undistort"));
+        let corners = [
+            undistort(self.corners[0])?,
+            undistort(self.corners[1])?,
+            undistort(self.corners[2])?,
+            undistort(self.corners[3])?,
+        ];
+        let homography = homography::from_corners(&corners)
+            .ok_or(Error::DegeneratePose("undistorted corners don't form a quadrilateral"))?;
+        Ok(TagDetection {
+            center: undistort(self.center)?,
+            corners,
+            homography,
+            ..*self
+        })
     }
 }
 
-#[derive(Clone, Copy)]
+// Mirrors `apriltag_quad_thresh_params`. `cos_critical_rad` is left out, since it's always derived
+// from `critical_rad`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(dead_code)]
+pub struct QuadThresholdParams {
+    pub min_cluster_pixels: u32,
+    pub max_nmaxima: u32,
+    pub critical_rad: f32,
+    pub max_line_fit_mse: f32,
+    pub min_white_black_diff: u32,
+    pub deglitch: bool,
+}
+
+impl Default for QuadThresholdParams {
+    fn default() -> QuadThresholdParams {
+        // Same as apriltag_detector_create
+        QuadThresholdParams {
+            min_cluster_pixels: 5,
+            max_nmaxima: 10,
+            critical_rad: 10.0 * std::f32::consts::PI / 180.0,
+            max_line_fit_mse: 10.0,
+            min_white_black_diff: 5,
+            deglitch: false,
+        }
+    }
+}
+
+impl QuadThresholdParams {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.min_cluster_pixels > i32::MAX as u32 {
+            return Err(Error::InvalidConfig { field: "min_cluster_pixels", reason: "too large" });
+        }
+        if self.max_nmaxima == 0 {
+            return Err(Error::InvalidConfig { field: "max_nmaxima", reason: "must be at least 1" });
+        }
+        if self.max_nmaxima > i32::MAX as u32 {
+            return Err(Error::InvalidConfig { field: "max_nmaxima", reason: "too large" });
+        }
+        if !(self.critical_rad.is_finite() && (0.0..=std::f32::consts::PI).contains(&self.critical_rad)) {
+            return Err(Error::InvalidConfig { field: "critical_rad", reason: "must be between 0 and pi" });
+        }
+        if !(self.max_line_fit_mse.is_finite() && self.max_line_fit_mse >= 0.0) {
+            return Err(Error::InvalidConfig { field: "max_line_fit_mse", reason: "must be non-negative" });
+        }
+        if self.min_white_black_diff > 255 {
+            return Err(Error::InvalidConfig { field: "min_white_black_diff", reason: "must be at most 255" });
+        }
+        Ok(())
+    }
+
+    fn from_raw(qtp: &apriltag_quad_thresh_params) -> QuadThresholdParams {
+        QuadThresholdParams {
+            min_cluster_pixels: qtp.min_cluster_pixels as u32,
+            max_nmaxima: qtp.max_nmaxima as u32,
+            critical_rad: qtp.critical_rad,
+            max_line_fit_mse: qtp.max_line_fit_mse,
+            min_white_black_diff: qtp.min_white_black_diff as u32,
+            deglitch: qtp.deglitch != 0,
+        }
+    }
+
+    fn write_raw(&self, qtp: &mut apriltag_quad_thresh_params) {
+        qtp.min_cluster_pixels = self.min_cluster_pixels as i32;
+        qtp.max_nmaxima = self.max_nmaxima as i32;
+        qtp.critical_rad = self.critical_rad;
+        qtp.cos_critical_rad = self.critical_rad.cos();
+        qtp.max_line_fit_mse = self.max_line_fit_mse;
+        qtp.min_white_black_diff = self.min_white_black_diff as i32;
+        qtp.deglitch = self.deglitch as i32;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(dead_code)]
 pub struct DetectorConfig {
     pub threads: u32,
@@ -267,6 +923,7 @@ pub struct DetectorConfig {
     pub refine_edges: bool,
     pub decode_sharpening: f64,
     pub debug: bool,
+    pub quad_threshold: QuadThresholdParams,
 }
 
 impl Default for DetectorConfig {
@@ -278,7 +935,29 @@ impl Default for DetectorConfig {
             refine_edges: false,
             decode_sharpening: 0.25,
             debug: false,
+            quad_threshold: QuadThresholdParams::default(),
+        }
+    }
+}
+
+impl DetectorConfig {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.threads == 0 {
+            return Err(Error::InvalidConfig { field: "threads", reason: "must be at least 1" });
+        }
+        if self.threads > i32::MAX as u32 {
+            return Err(Error::InvalidConfig { field: "threads", reason: "too large" });
+        }
+        if !(self.quad_decimate.is_finite() && self.quad_decimate >= 1.0) {
+            return Err(Error::InvalidConfig { field: "quad_decimate", reason: "must be at least 1.0" });
+        }
+        if !self.quad_sigma.is_finite() {
+            return Err(Error::InvalidConfig { field: "quad_sigma", reason: "must be finite" });
         }
+        if !(self.decode_sharpening.is_finite() && self.decode_sharpening >= 0.0) {
+            return Err(Error::InvalidConfig { field: "decode_sharpening", reason: "must be non-negative" });
+        }
+        self.quad_threshold.validate()
     }
 }
 
@@ -289,6 +968,12 @@ pub struct Detector {
 
 unsafe impl Send for Detector {}
 
+impl Default for Detector {
+    fn default() -> Detector {
+        Detector::new()
+    }
+}
+
 impl Drop for Detector {
     fn drop(&mut self) {
         unsafe {
@@ -300,38 +985,142 @@ impl Drop for Detector {
 #[allow(dead_code)]
 impl Detector {
     pub fn new() -> Detector {
+        Detector::try_new().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new() -> Result<Detector, Error> {
         unsafe {
             let ptr = apriltag_detector_create();
-            Detector {
-                raw: ptr,
+            if ptr.is_null() {
+                return Err(Error::AllocationFailed("detector"));
             }
+            Ok(Detector {
+                raw: ptr,
+            })
         }
     }
 
-    pub fn new_with_threads(n: i32) -> Detector {
+    pub fn new_with_threads(n: u32) -> Detector {
+        Detector::try_new_with_threads(n).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new_with_threads(n: u32) -> Result<Detector, Error> {
+        let mut detector = Detector::try_new()?;
+        detector.set_threads(n)?;
+        Ok(detector)
+    }
+
+    pub fn from_config(cfg: DetectorConfig) -> Detector {
+        Detector::try_from_config(cfg).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_from_config(cfg: DetectorConfig) -> Result<Detector, Error> {
+        let mut detector = Detector::try_new()?;
+        detector.set_config(&cfg)?;
+        Ok(detector)
+    }
+
+    pub fn config(&self) -> DetectorConfig {
         unsafe {
-            let ptr = apriltag_detector_create();
-            (*ptr).nthreads = n;
-            Detector {
-                raw: ptr,
+            let ptr = self.raw;
+            DetectorConfig {
+                threads: (*ptr).nthreads as u32,
+                quad_decimate: (*ptr).quad_decimate,
+                quad_sigma: (*ptr).quad_sigma,
+                refine_edges: (*ptr).refine_edges,
+                decode_sharpening: (*ptr).decode_sharpening,
+                debug: (*ptr).debug,
+                quad_threshold: QuadThresholdParams::from_raw(&(*ptr).qtp),
             }
         }
-
     }
 
-    pub fn from_config(cfg: DetectorConfig) -> Detector {
+    // Nothing is written unless the whole config is valid and the worker pool could be resized, so
+    // a failed call leaves the detector as it was.
+    pub fn set_config(&mut self, cfg: &DetectorConfig) -> Result<(), Error> {
+        cfg.validate()?;
+        self.resize_worker_pool(cfg.threads as i32)?;
         unsafe {
-            let ptr = apriltag_detector_create();
+            let ptr = self.raw;
             (*ptr).nthreads = cfg.threads as i32;
             (*ptr).quad_decimate = cfg.quad_decimate;
             (*ptr).quad_sigma = cfg.quad_sigma;
             (*ptr).refine_edges = cfg.refine_edges;
             (*ptr).decode_sharpening = cfg.decode_sharpening;
             (*ptr).debug = cfg.debug;
-            Detector {
-                raw: ptr,
+            cfg.quad_threshold.write_raw(&mut (*ptr).qtp);
+        }
+        Ok(())
+    }
+
+    // The worker pool is sized when it's created, so it has to be replaced whenever nthreads
+    // changes. The old pool is only destroyed once the new one exists.
+    fn resize_worker_pool(&mut self, nthreads: i32) -> Result<(), Error> {
+        unsafe {
+            let ptr = self.raw;
+            if !(*ptr).wp.is_null() && workerpool_get_nthreads((*ptr).wp) == nthreads {
+                return Ok(());
+            }
+            let wp = workerpool_create(nthreads);
+            if wp.is_null() {
+                return Err(Error::AllocationFailed("worker pool"));
+            }
+            if !(*ptr).wp.is_null() {
+                workerpool_destroy((*ptr).wp);
             }
+            (*ptr).wp = wp;
         }
+        Ok(())
+    }
+
+    pub fn set_threads(&mut self, threads: u32) -> Result<(), Error> {
+        let mut cfg = self.config();
+        cfg.threads = threads;
+        self.set_config(&cfg)
+    }
+
+    pub fn set_quad_decimate(&mut self, quad_decimate: f32) -> Result<(), Error> {
+        let mut cfg = self.config();
+        cfg.quad_decimate = quad_decimate;
+        self.set_config(&cfg)
+    }
+
+    pub fn set_quad_sigma(&mut self, quad_sigma: f32) -> Result<(), Error> {
+        let mut cfg = self.config();
+        cfg.quad_sigma = quad_sigma;
+        self.set_config(&cfg)
+    }
+
+    pub fn set_refine_edges(&mut self, refine_edges: bool) {
+        unsafe {
+            (*self.raw).refine_edges = refine_edges;
+        }
+    }
+
+    pub fn set_decode_sharpening(&mut self, decode_sharpening: f64) -> Result<(), Error> {
+        let mut cfg = self.config();
+        cfg.decode_sharpening = decode_sharpening;
+        self.set_config(&cfg)
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        unsafe {
+            (*self.raw).debug = debug;
+        }
+    }
+
+    pub fn quad_threshold_params(&self) -> QuadThresholdParams {
+        unsafe {
+            QuadThresholdParams::from_raw(&(*self.raw).qtp)
+        }
+    }
+
+    pub fn set_quad_threshold_params(&mut self, params: &QuadThresholdParams) -> Result<(), Error> {
+        params.validate()?;
+        unsafe {
+            params.write_raw(&mut (*self.raw).qtp);
+        }
+        Ok(())
     }
 
     pub fn add_with_bits(&mut self, fam: TagFamily, bits: u8) {
@@ -352,7 +1141,14 @@ impl Detector {
         }
     }
 
-    pub fn detect<T: AsRef<[u8]>>(&mut self, image: ImageU8<T>) -> Vec<Detection> {
+    /// Detections can only come from families added with `add`, which are all known to this
+    /// crate, so every detection `detect_raw` would return is converted.
+    pub fn detect<T: AsRef<[u8]>>(&mut self, image: ImageU8<T>) -> Vec<TagDetection> {
+        self.detect_raw(image).iter().filter_map(|det| TagDetection::try_from(det).ok()).collect()
+    }
+
+    /// Like `detect`, but keeps each detection in the memory libapriltag allocated for it.
+    pub fn detect_raw<T: AsRef<[u8]>>(&mut self, image: ImageU8<T>) -> Vec<Detection> {
         unsafe {
             let mut img_u8 = image.as_image_u8();
             let img_ptr = (&mut img_u8) as *mut image_u8_t;
@@ -366,7 +1162,11 @@ impl Detector {
                 zarray_get__extern(arr, i, det.as_mut_ptr() as *mut ::std::os::raw::c_void);
                 // let detection_p: *mut apriltag_detection_t = 
                 //     (*arr).data.add(n * std::mem::size_of::<*mut apriltag_detection_t>()) as *mut apriltag_detection_t;
-                let detection = Detection::from_raw(det.assume_init());
+                let mut detection = Detection::from_raw(det.assume_init());
+                if image.origin() != (0, 0) {
+                    let (dx, dy) = image.origin();
+                    detection.translate(dx as f64, dy as f64);
+                }
                 out.push(detection);
             }
             zarray_destroy__extern(arr);
@@ -0,0 +1,230 @@
+use std::f64::consts::FRAC_PI_2;
+
+use crate::detector::{CameraIntrinsics, Point};
+
+/// Lens distortion coefficients, in OpenCV's conventions.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Distortion {
+    /// An ideal pinhole camera.
+    #[default]
+    None,
+    /// Radial and tangential distortion, as used by OpenCV's `calibrateCamera`.
+    BrownConrady { k1: f64, k2: f64, k3: f64, p1: f64, p2: f64 },
+    /// The equidistant fisheye model, as used by OpenCV's `fisheye` module. With all coefficients
+    /// zero it's the plain equidistant projection, which only covers the half-space in front of
+    /// the camera.
+    KannalaBrandt { k1: f64, k2: f64, k3: f64, k4: f64 },
+}
+
+// Undistortion is solved iteratively. Both models converge in a handful of steps for any lens
+// that's been calibrated sensibly, so running out of iterations means the point can't be
+// undistorted.
+const MAX_ITERATIONS: usize = 50;
+const TOLERANCE: f64 = 1e-14;
+
+impl Distortion {
+    /// Distorts a point on the normalized image plane (z = 1).
+    pub fn distort(&self, p: Point) -> Point {
+        let [x, y] = p;
+        match *self {
+            Distortion::None => p,
+            Distortion::BrownConrady { k1, k2, k3, p1, p2 } => {
+                let r2 = x * x + y * y;
+                let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+                [
+                    x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x),
+                    y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y,
+                ]
+            }
+            Distortion::KannalaBrandt { k1, k2, k3, k4 } => {
+                let r = (x * x + y * y).sqrt();
+                if r < f64::EPSILON {
+                    return p;
+                }
+                let theta = r.atan();
+                let t2 = theta * theta;
+                let theta_d = theta * (1.0 + t2 * (k1 + t2 * (k2 + t2 * (k3 + t2 * k4))));
+                [x * theta_d / r, y * theta_d / r]
+            }
+        }
+    }
+
+    /// Inverse of `distort`, or `None` if the iteration doesn't converge. Kannala-Brandt also
+    /// gives `None` for points that would come from 90 degrees or more off the optical axis.
+    pub fn undistort(&self, p: Point) -> Option<Point> {
+        let [xd, yd] = p;
+        if !(xd.is_finite() && yd.is_finite()) {
+            return None;
+        }
+        match *self {
+            Distortion::None => Some(p),
+            Distortion::BrownConrady { k1, k2, k3, p1, p2 } => {
+                // Fixed-point iteration, as in OpenCV's undistortPoints
+                let (mut x, mut y) = (xd, yd);
+                let mut converged = false;
+                for _ in 0..MAX_ITERATIONS {
+                    let r2 = x * x + y * y;
+                    let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+                    let dx = 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+                    let dy = p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+                    let (nx, ny) = ((xd - dx) / radial, (yd - dy) / radial);
+                    converged = (nx - x).abs() + (ny - y).abs() < TOLERANCE;
+                    (x, y) = (nx, ny);
+                    if converged {
+                        break;
+                    }
+                }
+                // Strong distortion makes the iteration oscillate towards the edge of the image,
+                // where damped Newton steps still get there
+                if !converged {
+                    [x, y] = self.undistort_newton(p)?;
+                }
+                // The root has to be on the near side of the first fold, where the lens still maps
+                // outwards. With k2 > 0 it can turn outwards again, past a stretch of the image
+                // that nothing maps to.
+                let ([u, v], jacobian) = self.distort_with_jacobian([x, y]);
+                let determinant = jacobian[0][0] * jacobian[1][1] - jacobian[0][1] * jacobian[1][0];
+                let unfolded = radial_increases_up_to(k1, k2, k3, x * x + y * y);
+                ((u - xd).hypot(v - yd) < 1e-9 && determinant > 0.0 && unfolded).then_some([x, y])
+            }
+            Distortion::KannalaBrandt { k1, k2, k3, k4 } => {
+                let theta_d = (xd * xd + yd * yd).sqrt();
+                if theta_d < f64::EPSILON {
+                    return Some(p);
+                }
+                // Newton's method on theta_d = theta * (1 + k1 theta^2 + ...)
+                let mut theta = theta_d;
+                for _ in 0..MAX_ITERATIONS {
+                    let t2 = theta * theta;
+                    let f = theta * (1.0 + t2 * (k1 + t2 * (k2 + t2 * (k3 + t2 * k4)))) - theta_d;
+                    let df = 1.0 + t2 * (3.0 * k1 + t2 * (5.0 * k2 + t2 * (7.0 * k3 + t2 * 9.0 * k4)));
+                    let step = f / df;
+                    theta -= step;
+                    if step.abs() < TOLERANCE {
+                        break;
+                    }
+                }
+                // tan() would fold anything past 90 degrees back onto the image plane
+                let t2 = theta * theta;
+                let residual = theta * (1.0 + t2 * (k1 + t2 * (k2 + t2 * (k3 + t2 * k4)))) - theta_d;
+                if !(0.0..FRAC_PI_2).contains(&theta) || residual.abs() > 1e-9 * theta_d.max(1.0) {
+                    return None;
+                }
+                let scale = theta.tan() / theta_d;
+                Some([xd * scale, yd * scale])
+            }
+        }
+    }
+
+    // Brown-Conrady distortion of `p` and its derivative, as rows of d(u, v)/d(x, y).
+    fn distort_with_jacobian(&self, p: Point) -> (Point, [[f64; 2]; 2]) {
+        let Distortion::BrownConrady { k1, k2, k3, p1, p2 } = *self else {
+            return (self.distort(p), [[1.0, 0.0], [0.0, 1.0]]);
+        };
+        let [x, y] = p;
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+        let slope = k1 + r2 * (2.0 * k2 + 3.0 * r2 * k3);
+        (
+            self.distort(p),
+            [
+                [radial + 2.0 * x * x * slope + 2.0 * p1 * y + 6.0 * p2 * x, 2.0 * x * y * slope + 2.0 * p1 * x + 2.0 * p2 * y],
+                [2.0 * x * y * slope + 2.0 * p1 * x + 2.0 * p2 * y, radial + 2.0 * y * y * slope + 6.0 * p1 * y + 2.0 * p2 * x],
+            ],
+        )
+    }
+
+    // Newton's method on distort(x) = p, starting from p and halving any step that doesn't bring
+    // the residual down.
+    fn undistort_newton(&self, p: Point) -> Option<Point> {
+        let residual = |q: Point| {
+            let [u, v] = self.distort(q);
+            (u - p[0]).hypot(v - p[1])
+        };
+        let mut x = p;
+        let mut current = residual(x);
+        for _ in 0..MAX_ITERATIONS {
+            let ([u, v], [[a, b], [c, d]]) = self.distort_with_jacobian(x);
+            let determinant = a * d - b * c;
+            if !determinant.is_normal() {
+                return None;
+            }
+            let (ru, rv) = (u - p[0], v - p[1]);
+            let mut step = [(d * ru - b * rv) / determinant, (a * rv - c * ru) / determinant];
+            loop {
+                let next = [x[0] - step[0], x[1] - step[1]];
+                let error = residual(next);
+                if error < current || step[0].abs() + step[1].abs() < TOLERANCE {
+                    x = next;
+                    current = error;
+                    break;
+                }
+                step = [step[0] / 2.0, step[1] / 2.0];
+            }
+            if step[0].abs() + step[1].abs() < TOLERANCE {
+                break;
+            }
+        }
+        Some(x)
+    }
+}
+
+// Whether r (1 + k1 r^2 + k2 r^4 + k3 r^6) keeps increasing up to r^2 = s. Its derivative is a
+// cubic in r^2, so it's enough to check it at s and at the turning points before that.
+fn radial_increases_up_to(k1: f64, k2: f64, k3: f64, s: f64) -> bool {
+    let slope = |s: f64| 1.0 + s * (3.0 * k1 + s * (5.0 * k2 + s * 7.0 * k3));
+    // Roots of 3 k1 + 10 k2 s + 21 k3 s^2
+    let turning = if k3 != 0.0 {
+        let root = (100.0 * k2 * k2 - 252.0 * k1 * k3).sqrt();
+        [(-10.0 * k2 - root) / (42.0 * k3), (-10.0 * k2 + root) / (42.0 * k3)]
+    } else {
+        [-3.0 * k1 / (10.0 * k2), f64::NAN]
+    };
+    slope(s) > 0.0 && turning.into_iter().filter(|t| *t > 0.0 && *t < s).all(|t| slope(t) > 0.0)
+}
+
+impl CameraIntrinsics {
+    pub fn new(fx: f64, fy: f64, cx: f64, cy: f64) -> CameraIntrinsics {
+        CameraIntrinsics {
+            fx,
+            fy,
+            cx,
+            cy,
+            distortion: Distortion::None,
+        }
+    }
+
+    pub fn with_distortion(self, distortion: Distortion) -> CameraIntrinsics {
+        CameraIntrinsics { distortion, ..self }
+    }
+
+    /// The same camera without its distortion, i.e. the one that sees undistorted pixels.
+    pub fn pinhole(&self) -> CameraIntrinsics {
+        self.with_distortion(Distortion::None)
+    }
+
+    /// Moves a pixel to where an ideal pinhole camera with the same focal length and center would
+    /// have seen it, or `None` if that can't be worked out. See `Distortion::undistort`.
+    pub fn undistort_point(&self, p: Point) -> Option<Point> {
+        if self.distortion == Distortion::None {
+            return Some(p);
+        }
+        let [x, y] = self.distortion.undistort([(p[0] - self.cx) / self.fx, (p[1] - self.cy) / self.fy])?;
+        Some([x * self.fx + self.cx, y * self.fy + self.cy])
+    }
+
+    /// Inverse of `undistort_point`.
+    pub fn distort_point(&self, p: Point) -> Point {
+        if self.distortion == Distortion::None {
+            return p;
+        }
+        let [x, y] = self.distortion.distort([(p[0] - self.cx) / self.fx, (p[1] - self.cy) / self.fy]);
+        [x * self.fx + self.cx, y * self.fy + self.cy]
+    }
+}
+
+/// Undistorts pixel coordinates in bulk. See `CameraIntrinsics::undistort_point`.
+pub fn undistort_points(intrinsics: &CameraIntrinsics, points: &[Point]) -> Vec<Option<Point>> {
+    points.iter().map(|&p| intrinsics.undistort_point(p)).collect()
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The image buffer is shorter than its dimensions and stride require.
+    BufferTooSmall { needed: usize, actual: usize },
+    /// The dimensions can't describe an image, either because the stride is smaller than the width
+    /// or because they don't fit in libapriltag's `int` fields.
+    InvalidDimensions { width: u32, height: u32, stride: u32 },
+    /// A requested region doesn't fit inside the image it was taken from.
+    OutOfBounds { x: u32, y: u32, width: u32, height: u32 },
+    /// libapriltag returned NULL when asked to allocate the named object.
+    AllocationFailed(&'static str),
+    /// A configuration value is outside of the range libapriltag accepts.
+    InvalidConfig { field: &'static str, reason: &'static str },
+    /// The id is past the end of the family's code list.
+    InvalidId { id: u32, count: u32 },
+    /// The name doesn't match any tag family.
+    UnknownFamily(String),
+    /// Pose estimation produced something that isn't a rigid transform.
+    DegeneratePose(&'static str),
+    /// A field layout couldn't be parsed.
+    InvalidLayout(String),
+    /// The calibration frames don't contain enough information to solve for the camera.
+    CalibrationFailed(&'static str),
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+    /// A file isn't a PNM image this crate can read.
+    InvalidPnm(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BufferTooSmall { needed, actual } => {
+                write!(f, "image buffer holds {} bytes, but {} are needed", actual, needed)
+            }
+            Error::InvalidDimensions { width, height, stride } => {
+                write!(f, "invalid image dimensions {}x{} with stride {}", width, height, stride)
+            }
+            Error::OutOfBounds { x, y, width, height } => {
+                write!(f, "region {}x{}+{}+{} is out of bounds", width, height, x, y)
+            }
+            Error::AllocationFailed(what) => write!(f, "failed to allocate {}", what),
+            Error::InvalidConfig { field, reason } => write!(f, "invalid {}: {}", field, reason),
+            Error::InvalidId { id, count } => {
+                write!(f, "tag id {} is out of range for a family of {} tags", id, count)
+            }
+            Error::UnknownFamily(name) => write!(f, "unknown tag family \"{}\"", name),
+            Error::DegeneratePose(reason) => write!(f, "degenerate pose: {}", reason),
+            Error::InvalidLayout(reason) => write!(f, "invalid field layout: {}", reason),
+            Error::CalibrationFailed(reason) => write!(f, "calibration failed: {}", reason),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::InvalidPnm(reason) => write!(f, "invalid PNM image: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
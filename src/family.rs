@@ -1,14 +1,33 @@
 use crate::native::*;
+use crate::error::Error;
+use crate::image::ImageU8;
 
-use std::sync::OnceLock;
+use std::ffi::CString;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
 
 #[allow(dead_code)]
 pub struct Family {
     tag_family: TagFamily,
     raw: *mut apriltag_family_t,
+    custom: Option<CustomStorage>,
+}
+
+// Backing memory for a family built in Rust. `raw` points into a Box, and its arrays point into
+// these vectors.
+struct CustomStorage {
+    name: String,
+    _c_name: CString,
+    _codes: Vec<u64>,
+    _bit_x: Vec<u32>,
+    _bit_y: Vec<u32>,
 }
 
 impl Family {
+    /// # Safety
+    /// The family is shared by every detector that uses it, so it must not be modified or freed
+    /// through the returned pointer.
     #[allow(dead_code)]
     pub unsafe fn into_raw(&self) -> *mut apriltag_family_t {
         self.raw
@@ -37,6 +56,9 @@ impl Drop for Family {
             TagFamily::TagCustom48h12 => destroy!(custom48h12, self.raw),
             TagFamily::TagStandard41h12 => destroy!(standard41h12, self.raw),
             TagFamily::TagStandard52h13 => destroy!(standard52h13, self.raw),
+            TagFamily::Custom(_) => unsafe {
+                drop(Box::from_raw(self.raw));
+            },
         }
     }
 }
@@ -66,8 +88,132 @@ static TAG_STANDARD41H12: OnceLock<Family> = OnceLock::new();
 #[allow(dead_code)]
 static TAG_STANDARD52H13: OnceLock<Family> = OnceLock::new();
 
+// Custom families are leaked on registration, so that they live exactly as long as the built-in
+// ones and `TagFamily` can stay `Copy`.
+static CUSTOM_FAMILIES: Mutex<Vec<&'static Family>> = Mutex::new(Vec::new());
+
+fn custom_families() -> std::sync::MutexGuard<'static, Vec<&'static Family>> {
+    CUSTOM_FAMILIES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Handle to a family registered with `CustomFamily::build`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CustomFamilyId(usize);
+
+pub(crate) struct CustomFamilyData {
+    pub name: String,
+    pub codes: Vec<u64>,
+    pub bit_x: Vec<u32>,
+    pub bit_y: Vec<u32>,
+    pub width_at_border: i32,
+    pub total_width: i32,
+    pub reversed_border: bool,
+    pub min_hamming: i32,
+}
+
+// Names are checked for uniqueness under the same lock that registers them, so two threads can't
+// race to claim the same one.
+pub(crate) fn register_custom(data: CustomFamilyData) -> Result<TagFamily, Error> {
+    let c_name = CString::new(data.name.as_str())
+        .map_err(|_| Error::InvalidConfig { field: "name", reason: "must not contain NUL" })?;
+    let mut families = custom_families();
+    let taken = TagFamily::ALL.iter().any(|fam| fam.name().eq_ignore_ascii_case(&data.name))
+        || families.iter().any(|fam| fam.custom.as_ref().is_some_and(|c| c.name.eq_ignore_ascii_case(&data.name)));
+    if taken {
+        return Err(Error::InvalidConfig { field: "name", reason: "a family with this name already exists" });
+    }
+
+    let mut storage = CustomStorage {
+        name: data.name,
+        _c_name: c_name,
+        _codes: data.codes,
+        _bit_x: data.bit_x,
+        _bit_y: data.bit_y,
+    };
+    let raw = Box::into_raw(Box::new(apriltag_family_t {
+        ncodes: storage._codes.len() as u32,
+        codes: storage._codes.as_mut_ptr(),
+        width_at_border: data.width_at_border,
+        total_width: data.total_width,
+        reversed_border: data.reversed_border,
+        nbits: storage._bit_x.len() as u32,
+        bit_x: storage._bit_x.as_mut_ptr(),
+        bit_y: storage._bit_y.as_mut_ptr(),
+        h: data.min_hamming,
+        name: storage._c_name.as_ptr() as *mut _,
+        impl_: std::ptr::null_mut(),
+    }));
+    let tag_family = TagFamily::Custom(CustomFamilyId(families.len()));
+    families.push(Box::leak(Box::new(Family {
+        tag_family,
+        raw,
+        custom: Some(storage),
+    })));
+    Ok(tag_family)
+}
+
+// Rotates a code by 90 degrees, the same way libapriltag does. This relies on the bits being
+// ordered so that each quarter of them maps onto the next under rotation, with any odd bit (the
+// center) last.
+// Why a layout fails `is_clockwise`, for the errors of the builders that check it.
+pub(crate) const CLOCKWISE_LAYOUT: &str = "each quarter of the bits must be the previous one rotated clockwise, from (x, y) to (width_at_border - 1 - y, x), with any center bit last";
+
+// Whether `bits` are in the order `rotate90` assumes. Rotating clockwise about the center of the
+// border maps (x, y) to (c - y, x). The other winding still decodes, but with the rotation (and so
+// the corners) reported wrong. `bits` must have a multiple of 4 entries, or one more.
+pub(crate) fn is_clockwise(bits: &[(i32, i32)], width_at_border: u32) -> bool {
+    let nbits = bits.len();
+    let c = width_at_border as i32 - 1;
+    let quarter = nbits / 4;
+    let rotated = (0..3 * quarter).all(|i| (c - bits[i].1, bits[i].0) == bits[i + quarter]);
+    let centered = nbits.is_multiple_of(4) || (2 * bits[nbits - 1].0 == c && 2 * bits[nbits - 1].1 == c);
+    rotated && centered
+}
+
+pub(crate) fn rotate90(code: u64, nbits: u32) -> u64 {
+    let (p, l) = if nbits % 4 == 1 {(nbits - 1, 1)} else {(nbits, 0)};
+    let rotated = ((code >> l) << (p / 4 + l)) | ((code >> (3 * p / 4 + l)) << l) | (code & l as u64);
+    if nbits == 64 {rotated} else {rotated & ((1u64 << nbits) - 1)}
+}
+
+// Lays out a code on a `total_width` square grid (row-major, `true` for white), following
+// `apriltag_to_image`. Bit positions are relative to the top-left corner of the black border, most
+// significant bit first.
+pub(crate) fn draw_pattern(code: u64, bits: &[(i32, i32)], width_at_border: i32, total_width: i32, reversed_border: bool) -> Vec<bool> {
+    let total = total_width;
+    let mut cells = vec![false; (total * total) as usize];
+    let mut set = |x: i32, y: i32| {
+        if (0..total).contains(&x) && (0..total).contains(&y) {
+            cells[(y * total + x) as usize] = true;
+        }
+    };
+
+    let white_border_width = width_at_border + if reversed_border {0} else {2};
+    let white_border_start = (total - white_border_width) / 2;
+    let white_border_end = total - 1 - white_border_start;
+    for i in 0..white_border_width - 1 {
+        set(white_border_start + i, white_border_start);
+        set(white_border_end, white_border_start + i);
+        set(white_border_start + i + 1, white_border_end);
+        set(white_border_start, white_border_start + 1 + i);
+    }
+
+    let border_start = (total - width_at_border) / 2;
+    let nbits = bits.len();
+    for (i, &(x, y)) in bits.iter().enumerate() {
+        if code & (1 << (nbits - i - 1)) != 0 {
+            set(x + border_start, y + border_start);
+        }
+    }
+    cells
+}
+
+pub(crate) fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
 #[allow(dead_code)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TagFamily {
     Tag16h5,
     Tag25h9,
@@ -78,6 +224,25 @@ pub enum TagFamily {
     TagCustom48h12,
     TagStandard41h12,
     TagStandard52h13,
+    Custom(CustomFamilyId),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderOptions {
+    /// Pixels per cell.
+    pub scale: u32,
+    /// Extra white cells added around the tag on every side, on top of any white border that is
+    /// part of the family's own layout.
+    pub quiet_zone: u32,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            scale: 1,
+            quiet_zone: 0,
+        }
+    }
 }
 
 use paste::paste;
@@ -88,6 +253,7 @@ macro_rules! get_family {
                 Family {
                     tag_family: TagFamily::[<Tag $fam:camel>],
                     raw: unsafe {[<tag $fam:camel _create>]()},
+                    custom: None,
                 }
             })
         }
@@ -95,8 +261,20 @@ macro_rules! get_family {
 }
 
 impl TagFamily {
+    pub const ALL: [TagFamily; 9] = [
+        TagFamily::Tag16h5,
+        TagFamily::Tag25h9,
+        TagFamily::Tag36h10,
+        TagFamily::Tag36h11,
+        TagFamily::TagCircle21h7,
+        TagFamily::TagCircle49h12,
+        TagFamily::TagCustom48h12,
+        TagFamily::TagStandard41h12,
+        TagFamily::TagStandard52h13,
+    ];
+
     #[allow(dead_code)]
-    pub fn family(&self) -> &Family {
+    pub fn family(&self) -> &'static Family {
         match self {
             TagFamily::Tag16h5 => get_family!(16h5),
             TagFamily::Tag25h9 => get_family!(25h9),
@@ -107,6 +285,184 @@ impl TagFamily {
             TagFamily::TagCustom48h12 => get_family!(custom48h12),
             TagFamily::TagStandard41h12 => get_family!(standard41h12),
             TagFamily::TagStandard52h13 => get_family!(standard52h13),
+            TagFamily::Custom(CustomFamilyId(index)) => custom_families()[*index],
+        }
+    }
+
+    /// The name libapriltag uses for the family, e.g. `"tag36h11"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TagFamily::Tag16h5 => "tag16h5",
+            TagFamily::Tag25h9 => "tag25h9",
+            TagFamily::Tag36h10 => "tag36h10",
+            TagFamily::Tag36h11 => "tag36h11",
+            TagFamily::TagCircle21h7 => "tagCircle21h7",
+            TagFamily::TagCircle49h12 => "tagCircle49h12",
+            TagFamily::TagCustom48h12 => "tagCustom48h12",
+            TagFamily::TagStandard41h12 => "tagStandard41h12",
+            TagFamily::TagStandard52h13 => "tagStandard52h13",
+            TagFamily::Custom(_) => {
+                self.family().custom.as_ref().map(|c| c.name.as_str()).unwrap_or_default()
+            }
+        }
+    }
+
+    /// Same as `name.parse()`, so the name can be in any case.
+    pub fn from_name(name: &str) -> Option<TagFamily> {
+        name.parse().ok()
+    }
+
+    // Built-in families first, then custom ones in the order they were registered
+    fn find(matches: impl Fn(&str) -> bool) -> Option<TagFamily> {
+        TagFamily::ALL.into_iter().find(|fam| matches(fam.name())).or_else(|| {
+            custom_families().iter()
+                .find(|fam| fam.custom.as_ref().is_some_and(|c| matches(&c.name)))
+                .map(|fam| fam.tag_family)
+        })
+    }
+
+    fn raw(&self) -> &apriltag_family_t {
+        unsafe {&*self.family().raw}
+    }
+
+    /// Number of distinct tags (ids) in the family.
+    pub fn code_count(&self) -> u32 {
+        self.raw().ncodes
+    }
+
+    /// Number of data bits in each tag.
+    pub fn bit_count(&self) -> u32 {
+        self.raw().nbits
+    }
+
+    /// Minimum Hamming distance between any two codes, including rotations.
+    pub fn min_hamming(&self) -> u32 {
+        self.raw().h as u32
+    }
+
+    /// Width of the tag in cells, including the outer white border.
+    pub fn total_width(&self) -> u32 {
+        self.raw().total_width as u32
+    }
+
+    /// Width in cells of the square whose corners are reported as detection corners.
+    pub fn width_at_border(&self) -> u32 {
+        self.raw().width_at_border as u32
+    }
+
+    /// Whether the border is white-inside-black (as in the Standard and Circle families) rather
+    /// than the classic black-inside-white.
+    pub fn reversed_border(&self) -> bool {
+        self.raw().reversed_border
+    }
+
+    /// The tag's cells in row-major order, `total_width` on a side, with `true` for white. This
+    /// follows `apriltag_to_image`, so the layout matches what the detector expects.
+    pub fn pattern(&self, id: u32) -> Result<Vec<bool>, Error> {
+        let fam = self.raw();
+        if id >= fam.ncodes {
+            return Err(Error::InvalidId { id, count: fam.ncodes });
+        }
+        let code = unsafe {*fam.codes.add(id as usize)};
+        // Bit positions can be "negative" (stored wrapped around as u32) for families with data
+        // outside the black border.
+        let bits: Vec<(i32, i32)> = (0..fam.nbits as usize).map(|i| unsafe {
+            (*fam.bit_x.add(i) as i32, *fam.bit_y.add(i) as i32)
+        }).collect();
+        Ok(draw_pattern(code, &bits, fam.width_at_border, fam.total_width, fam.reversed_border))
+    }
+
+    pub fn render(&self, id: u32) -> Result<ImageU8<Vec<u8>>, Error> {
+        self.render_with(id, &RenderOptions::default())
+    }
+
+    pub fn render_with(&self, id: u32, options: &RenderOptions) -> Result<ImageU8<Vec<u8>>, Error> {
+        if options.scale == 0 {
+            return Err(Error::InvalidConfig { field: "scale", reason: "must be at least 1" });
+        }
+        let cells = self.pattern(id)?;
+        let total = self.total_width() as usize;
+        let scale = options.scale as usize;
+        // libapriltag indexes pixels with an int, so the whole image has to fit in one
+        let too_large = || Error::InvalidConfig { field: "scale", reason: "scale and quiet_zone make the image too large" };
+        let side = (options.quiet_zone as usize).checked_mul(2)
+            .and_then(|margin| margin.checked_add(total))
+            .and_then(|padded| padded.checked_mul(scale))
+            .filter(|&side| side.checked_mul(side).is_some_and(|pixels| pixels <= i32::MAX as usize))
+            .ok_or_else(too_large)?;
+        let width = u32::try_from(side).map_err(|_| too_large())?;
+
+        let mut data = vec![255u8; side * side];
+        for (y, row) in data.chunks_exact_mut(side).enumerate() {
+            let cy = (y / scale).wrapping_sub(options.quiet_zone as usize);
+            if cy >= total {
+                continue;
+            }
+            for (x, px) in row.iter_mut().enumerate() {
+                let cx = (x / scale).wrapping_sub(options.quiet_zone as usize);
+                if cx < total && !cells[cy * total + cx] {
+                    *px = 0;
+                }
+            }
         }
+        ImageU8::try_new(width, width, data)
+    }
+
+    // Finds the family that owns `ptr`. Only families that have been created can match, which is
+    // fine, since a detection can only come from a family that was added to a detector.
+    pub(crate) fn from_raw(ptr: *const apriltag_family_t) -> Option<TagFamily> {
+        [
+            (&TAG_16H5, TagFamily::Tag16h5),
+            (&TAG_25H9, TagFamily::Tag25h9),
+            (&TAG_36H10, TagFamily::Tag36h10),
+            (&TAG_36H11, TagFamily::Tag36h11),
+            (&TAG_CIRCLE21H7, TagFamily::TagCircle21h7),
+            (&TAG_CIRCLE49H12, TagFamily::TagCircle49h12),
+            (&TAG_CUSTOM48H12, TagFamily::TagCustom48h12),
+            (&TAG_STANDARD41H12, TagFamily::TagStandard41h12),
+            (&TAG_STANDARD52H13, TagFamily::TagStandard52h13),
+        ].into_iter().find_map(|(cell, fam)| {
+            match cell.get() {
+                Some(family) if std::ptr::eq(family.raw, ptr) => Some(fam),
+                _ => None,
+            }
+        }).or_else(|| {
+            custom_families().iter()
+                .find(|family| std::ptr::eq(family.raw, ptr))
+                .map(|family| family.tag_family)
+        })
+    }
+}
+
+impl fmt::Display for TagFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// Accepts the canonical name in any case, so "tag36h11" and "Tag36H11" both work.
+impl FromStr for TagFamily {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<TagFamily, Error> {
+        TagFamily::find(|name| name.eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::UnknownFamily(s.to_string()))
+    }
+}
+
+// Families are (de)serialized by name rather than by variant, so that files stay readable by other
+// AprilTag tooling.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TagFamily {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TagFamily {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<TagFamily, D::Error> {
+        let name = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
     }
 }
@@ -0,0 +1,221 @@
+use crate::board::TagBoard;
+use crate::detector::{CameraIntrinsics, Pose, Rotation, TagDetection};
+use crate::error::Error;
+use crate::family::TagFamily;
+use crate::frames::Frame;
+use nalgebra::Matrix3;
+use std::collections::BTreeMap;
+
+/// Tag poses in a shared world frame, like WPILib's `AprilTagFieldLayout`.
+///
+/// Everything here follows WPILib's conventions: the world frame has x along the field, y to the
+/// left and z up, and each tag's frame has x pointing out of its face, y to the left and z up.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldLayout {
+    pub family: TagFamily,
+    /// Length of a side of the tags' black border, in the same units as the tag poses.
+    pub tag_size: f64,
+    pub field_length: f64,
+    pub field_width: f64,
+    tags: BTreeMap<u32, Pose>,
+}
+
+/// A camera pose in the world frame. The camera frame uses WPILib's convention too: x forward, y
+/// left and z up.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraPose {
+    pub pose: Pose,
+    /// Root-mean-square reprojection error, in pixels.
+    pub error: f64,
+    /// Ids of the tags the pose was solved from.
+    pub ids: Vec<u32>,
+}
+
+/// The result of `FieldLayout::localize`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Localization {
+    /// One estimate per detection that's on the field, solved from that tag alone. Tags whose
+    /// pose couldn't be solved are left out.
+    pub per_tag: Vec<CameraPose>,
+    /// A single estimate solved from every tag on the field at once.
+    pub combined: CameraPose,
+}
+
+impl FieldLayout {
+    pub fn new(family: TagFamily, tag_size: f64) -> FieldLayout {
+        FieldLayout {
+            family,
+            tag_size,
+            field_length: 0.0,
+            field_width: 0.0,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a tag, replacing any tag already in the layout with the same id.
+    pub fn add_tag(&mut self, id: u32, pose: Pose) {
+        self.tags.insert(id, pose);
+    }
+
+    pub fn remove_tag(&mut self, id: u32) -> Option<Pose> {
+        self.tags.remove(&id)
+    }
+
+    pub fn tag_pose(&self, id: u32) -> Option<Pose> {
+        self.tags.get(&id).copied()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.tags.keys().copied()
+    }
+
+    /// The whole field as one board, with the world frame as the board frame.
+    pub fn board(&self) -> TagBoard {
+        let to_apriltag = wpilib_from_apriltag_tag();
+        let mut board = TagBoard::new(self.family);
+        for (&id, pose) in &self.tags {
+            board.add_tag_with_pose(id, &(*pose * to_apriltag), self.tag_size);
+        }
+        board
+    }
+
+    /// Finds the camera's pose in the world from the detections in one frame.
+    ///
+    /// This is the camera itself, not the robot: compose with the inverse of the camera's pose on
+    /// the robot (`camera.pose * robot_to_camera.inverse()`) to get the robot's pose.
+    pub fn localize(&self, detections: &[TagDetection], intrinsics: &CameraIntrinsics) -> Result<Localization, Error> {
+        let board = self.board();
+        let to_world = |pose: Pose| pose.inverse().convert_child(Frame::CameraCv, Frame::WpiLib);
+
+        let per_tag = detections.iter()
+            .filter_map(|det| board.estimate_pose(std::slice::from_ref(det), intrinsics).ok())
+            .map(|estimate| CameraPose {
+                pose: to_world(estimate.pose),
+                error: estimate.error,
+                ids: estimate.ids,
+            })
+            .collect();
+        let combined = board.estimate_pose(detections, intrinsics)?;
+
+        Ok(Localization {
+            per_tag,
+            combined: CameraPose {
+                pose: to_world(combined.pose),
+                error: combined.error,
+                ids: combined.ids,
+            },
+        })
+    }
+}
+
+// Pose of libapriltag's tag frame (x right, y down, z into the tag) in WPILib's tag frame.
+fn wpilib_from_apriltag_tag() -> Pose {
+    let m = Matrix3::new(
+        0.0, 0.0, -1.0,
+        1.0, 0.0, 0.0,
+        0.0, -1.0, 0.0,
+    );
+    Pose {
+        rot: Rotation::from_matrix(m).expect("axis permutations are rotations"),
+        ..Pose::identity()
+    }
+}
+
+
+#[cfg(feature = "json")]
+mod wpilib {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Layout {
+        pub tags: Vec<Tag>,
+        pub field: Field,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Tag {
+        #[serde(rename = "ID")]
+        pub id: u32,
+        pub pose: Pose,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Pose {
+        pub translation: Translation,
+        pub rotation: Rotation,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Translation {
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Rotation {
+        pub quaternion: Quaternion,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "UPPERCASE")]
+    pub(super) struct Quaternion {
+        pub w: f64,
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Field {
+        pub length: f64,
+        pub width: f64,
+    }
+}
+
+#[cfg(feature = "json")]
+impl FieldLayout {
+    /// Parses the JSON format used by WPILib's `AprilTagFieldLayout`. That format doesn't record
+    /// the tags' family or size, so they have to be given (tag36h11 and 0.1651 m since 2024).
+    pub fn from_wpilib_json(json: &str, family: TagFamily, tag_size: f64) -> Result<FieldLayout, Error> {
+        let parsed: wpilib::Layout = serde_json::from_str(json)
+            .map_err(|e| Error::InvalidLayout(e.to_string()))?;
+        let mut layout = FieldLayout::new(family, tag_size);
+        layout.field_length = parsed.field.length;
+        layout.field_width = parsed.field.width;
+        for tag in parsed.tags {
+            let q = tag.pose.rotation.quaternion;
+            let rot = Rotation::from_quaternion(q.w, q.x, q.y, q.z)
+                .map_err(|_| Error::InvalidLayout(format!("tag {} has a zero quaternion", tag.id)))?;
+            let t = tag.pose.translation;
+            layout.add_tag(tag.id, Pose {
+                rot,
+                pos: crate::detector::Translation { x: t.x, y: t.y, z: t.z },
+            });
+        }
+        Ok(layout)
+    }
+
+    pub fn to_wpilib_json(&self) -> String {
+        let layout = wpilib::Layout {
+            tags: self.tags.iter().map(|(&id, pose)| {
+                let [w, x, y, z] = pose.rot.quaternion();
+                wpilib::Tag {
+                    id,
+                    pose: wpilib::Pose {
+                        translation: wpilib::Translation { x: pose.pos.x, y: pose.pos.y, z: pose.pos.z },
+                        rotation: wpilib::Rotation { quaternion: wpilib::Quaternion { w, x, y, z } },
+                    },
+                }
+            }).collect(),
+            field: wpilib::Field {
+                length: self.field_length,
+                width: self.field_width,
+            },
+        };
+        serde_json::to_string_pretty(&layout).expect("layouts are always representable as JSON")
+    }
+}
@@ -0,0 +1,70 @@
+use crate::detector::{Pose, Rotation};
+use nalgebra::Matrix3;
+
+/// Axis conventions for 3D frames.
+///
+/// Poses from libapriltag are in `CameraCv`. The others only differ from it by a rotation, so
+/// converting never moves anything; it only changes the coordinates things are described in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Frame {
+    /// x right, y down, z forward. Used by OpenCV and libapriltag.
+    CameraCv,
+    /// x right, y down, z forward. ROS REP-103's `_optical` frames, the same axes as `CameraCv`.
+    RosOptical,
+    /// x forward, y left, z up. ROS REP-103's body frames.
+    RosBody,
+    /// x forward, y left, z up. WPILib's robot and camera frames, the same axes as `RosBody`.
+    WpiLib,
+}
+
+impl Frame {
+    // Columns are this frame's axes, written in `CameraCv` coordinates
+    fn axes_in_cv(self) -> Matrix3<f64> {
+        match self {
+            Frame::CameraCv | Frame::RosOptical => Matrix3::identity(),
+            Frame::RosBody | Frame::WpiLib => Matrix3::new(
+                0.0, -1.0, 0.0,
+                0.0, 0.0, -1.0,
+                1.0, 0.0, 0.0,
+            ),
+        }
+    }
+
+    /// The rotation taking coordinates in `self` to coordinates in `other`.
+    pub fn rotation_to(self, other: Frame) -> Rotation {
+        let m = other.axes_in_cv().transpose() * self.axes_in_cv();
+        Rotation::from_matrix(m).expect("axis permutations are rotations")
+    }
+
+    /// Converts a point or direction from `self` coordinates to `other` coordinates.
+    pub fn convert_point(self, point: [f64; 3], other: Frame) -> [f64; 3] {
+        self.rotation_to(other).rotate(point)
+    }
+}
+
+impl Pose {
+    /// Re-expresses the pose with both of its frames changed from the `from` convention to `to`,
+    /// e.g. a detection's pose from `CameraCv` into `WpiLib`.
+    pub fn convert(&self, from: Frame, to: Frame) -> Pose {
+        self.convert_parent(from, to).convert_child(from, to)
+    }
+
+    /// Changes only the convention of the frame the pose is expressed in.
+    pub fn convert_parent(&self, from: Frame, to: Frame) -> Pose {
+        let change = Pose {
+            rot: from.rotation_to(to),
+            ..Pose::identity()
+        };
+        change * *self
+    }
+
+    /// Changes only the convention of the frame the pose describes.
+    pub fn convert_child(&self, from: Frame, to: Frame) -> Pose {
+        let change = Pose {
+            rot: to.rotation_to(from),
+            ..Pose::identity()
+        };
+        *self * change
+    }
+}
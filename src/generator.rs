@@ -0,0 +1,243 @@
+use crate::custom::CustomFamily;
+use crate::error::Error;
+use crate::family;
+
+/// Where each data bit of a tag sits, plus the border geometry around them.
+///
+/// Positions are in cells relative to the top-left corner of the black border, most significant
+/// bit first. They must be ordered so that rotating the tag clockwise by 90 degrees, as seen with
+/// y pointing down, maps each quarter of the bits onto the next, with the center bit (if the count
+/// is odd) last. That's the only ordering libapriltag decodes with the right rotation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    bits: Vec<(i32, i32)>,
+    width_at_border: u32,
+    total_width: u32,
+    reversed_border: bool,
+}
+
+impl Layout {
+    /// A `data_width` x `data_width` square of data inside a one-cell black border, inside a
+    /// one-cell white border, like tag16h5, tag25h9 and tag36h11.
+    pub fn classic(data_width: u32) -> Result<Layout, Error> {
+        if data_width == 0 || data_width > 8 {
+            return Err(Error::InvalidConfig { field: "data_width", reason: "must be between 1 and 8" });
+        }
+        let d = data_width as i32;
+        // A fundamental region for rotation about the center: the top-left quadrant for an even
+        // width, or a pinwheel blade for an odd one.
+        let half = d / 2;
+        let quarter: Vec<(i32, i32)> = (0..half)
+            .flat_map(|y| (0..(d + 1) / 2).map(move |x| (x, y)))
+            .collect();
+
+        let mut bits = Vec::with_capacity((d * d) as usize);
+        let mut current = quarter;
+        for _ in 0..4 {
+            bits.extend(current.iter().copied());
+            current = current.iter().map(|&(x, y)| (d - 1 - y, x)).collect();
+        }
+        if d % 2 == 1 {
+            bits.push((half, half));
+        }
+
+        Ok(Layout {
+            // Data starts one cell in from the outside of the black border
+            bits: bits.into_iter().map(|(x, y)| (x + 1, y + 1)).collect(),
+            width_at_border: data_width + 2,
+            total_width: data_width + 4,
+            reversed_border: false,
+        })
+    }
+
+    /// A layout with arbitrary bit positions. Fails if the bits aren't ordered as described on
+    /// `Layout`.
+    pub fn custom(bits: Vec<(i32, i32)>, width_at_border: u32, total_width: u32, reversed_border: bool) -> Result<Layout, Error> {
+        let nbits = bits.len();
+        if nbits == 0 || nbits > 64 || nbits % 4 > 1 {
+            return Err(Error::InvalidConfig { field: "bits", reason: "bit count must be at most 64, and a multiple of 4 or one more than one" });
+        }
+        if total_width < width_at_border || total_width > i32::MAX as u32 || !(total_width - width_at_border).is_multiple_of(2) {
+            return Err(Error::InvalidConfig { field: "total_width", reason: "must be at least width_at_border, with an even difference" });
+        }
+
+        if !family::is_clockwise(&bits, width_at_border) {
+            return Err(Error::InvalidConfig { field: "bits", reason: family::CLOCKWISE_LAYOUT });
+        }
+
+        Ok(Layout {
+            bits,
+            width_at_border,
+            total_width,
+            reversed_border,
+        })
+    }
+
+    pub fn bit_count(&self) -> u32 {
+        self.bits.len() as u32
+    }
+
+    pub fn bits(&self) -> &[(i32, i32)] {
+        &self.bits
+    }
+
+    pub fn width_at_border(&self) -> u32 {
+        self.width_at_border
+    }
+
+    pub fn total_width(&self) -> u32 {
+        self.total_width
+    }
+
+    pub fn reversed_border(&self) -> bool {
+        self.reversed_border
+    }
+
+    /// Number of edges between neighbouring cells of different colours that touch at least one data
+    /// bit. Codes with low complexity look like plain squares or stripes, which show up far more
+    /// often in real scenes than random patterns.
+    pub fn complexity(&self, code: u64) -> u32 {
+        let total = self.total_width as i32;
+        let cells = family::draw_pattern(code, &self.bits, self.width_at_border as i32, total, self.reversed_border);
+        let border_start = (total - self.width_at_border as i32) / 2;
+        let mut is_data = vec![false; cells.len()];
+        for &(x, y) in &self.bits {
+            let (x, y) = (x + border_start, y + border_start);
+            if (0..total).contains(&x) && (0..total).contains(&y) {
+                is_data[(y * total + x) as usize] = true;
+            }
+        }
+
+        let mut edges = 0;
+        for y in 0..total {
+            for x in 0..total {
+                let here = (y * total + x) as usize;
+                for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                    if nx >= total || ny >= total {
+                        continue;
+                    }
+                    let there = (ny * total + nx) as usize;
+                    if (is_data[here] || is_data[there]) && cells[here] != cells[there] {
+                        edges += 1;
+                    }
+                }
+            }
+        }
+        edges
+    }
+}
+
+/// Searches for a set of codes on a layout, in the spirit of the upstream Java
+/// `TagFamilyGenerator`.
+///
+/// Candidates are visited in a fixed pseudo-random order derived from `seed`, so the same settings
+/// always produce the same family. A candidate is kept if it is at least `min_hamming` bits away
+/// from every rotation of every code kept so far (and from its own rotations), and if its
+/// complexity is at least `min_complexity`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FamilyGenerator {
+    pub layout: Layout,
+    pub min_hamming: u32,
+    pub min_complexity: u32,
+    pub seed: u64,
+    /// Upper bound on the number of candidates tried. The search also stops after every possible
+    /// code has been visited once.
+    pub max_candidates: u64,
+    /// Stop as soon as this many codes have been found.
+    pub max_codes: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratedFamily {
+    pub layout: Layout,
+    pub codes: Vec<u64>,
+    pub min_hamming: u32,
+}
+
+impl FamilyGenerator {
+    pub fn new(layout: Layout, min_hamming: u32) -> FamilyGenerator {
+        let min_complexity = layout.bit_count() / 2;
+        FamilyGenerator {
+            layout,
+            min_hamming,
+            min_complexity,
+            seed: 0,
+            max_candidates: 1 << 24,
+            max_codes: None,
+        }
+    }
+
+    pub fn generate(&self) -> Result<GeneratedFamily, Error> {
+        let nbits = self.layout.bit_count();
+        if self.min_hamming == 0 || self.min_hamming > nbits {
+            return Err(Error::InvalidConfig { field: "min_hamming", reason: "must be between 1 and the number of bits" });
+        }
+        let mask = if nbits == 64 {u64::MAX} else {(1u64 << nbits) - 1};
+        let candidates = if nbits >= 64 {self.max_candidates} else {self.max_candidates.min(1 << nbits)};
+
+        // Any odd step visits every value mod 2^nbits exactly once before repeating
+        let mut state = self.seed;
+        let mut code = splitmix64(&mut state) & mask;
+        let step = (splitmix64(&mut state) | 1) & mask;
+
+        let mut codes = Vec::new();
+        // Every rotation of every accepted code, so each check is a flat scan
+        let mut rotations: Vec<u64> = Vec::new();
+        for _ in 0..candidates {
+            code = code.wrapping_add(step) & mask;
+            if self.accepts(code, &rotations) {
+                codes.push(code);
+                let mut rotated = code;
+                for _ in 0..4 {
+                    rotations.push(rotated);
+                    rotated = family::rotate90(rotated, nbits);
+                }
+                if self.max_codes.is_some_and(|max| codes.len() >= max) {
+                    break;
+                }
+            }
+        }
+
+        Ok(GeneratedFamily {
+            layout: self.layout.clone(),
+            codes,
+            min_hamming: self.min_hamming,
+        })
+    }
+
+    fn accepts(&self, code: u64, rotations: &[u64]) -> bool {
+        let nbits = self.layout.bit_count();
+        let mut rotated = code;
+        for _ in 0..3 {
+            rotated = family::rotate90(rotated, nbits);
+            if family::hamming_distance(code, rotated) < self.min_hamming {
+                return false;
+            }
+        }
+        rotations.iter().all(|&other| family::hamming_distance(code, other) >= self.min_hamming)
+            && self.layout.complexity(code) >= self.min_complexity
+    }
+}
+
+impl GeneratedFamily {
+    /// A builder for the generated family, ready to `build` and add to a detector.
+    pub fn to_custom_family(&self, name: impl Into<String>) -> CustomFamily {
+        let bit_x: Vec<i32> = self.layout.bits.iter().map(|&(x, _)| x).collect();
+        let bit_y: Vec<i32> = self.layout.bits.iter().map(|&(_, y)| y).collect();
+        CustomFamily::new(name)
+            .codes(self.codes.clone())
+            .bit_layout(&bit_x, &bit_y)
+            .width_at_border(self.layout.width_at_border)
+            .total_width(self.layout.total_width)
+            .reversed_border(self.layout.reversed_border)
+            .min_hamming(self.min_hamming)
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
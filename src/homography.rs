@@ -0,0 +1,79 @@
+use crate::detector::{Homography, Point};
+
+// Maps a point through `h`, returning None if it lands on the line at infinity.
+pub(crate) fn project(h: &Homography, p: Point) -> Option<Point> {
+    let x = h[0][0] * p[0] + h[0][1] * p[1] + h[0][2];
+    let y = h[1][0] * p[0] + h[1][1] * p[1] + h[1][2];
+    let z = h[2][0] * p[0] + h[2][1] * p[1] + h[2][2];
+    if z.abs() < f64::EPSILON {
+        return None;
+    }
+    Some([x / z, y / z])
+}
+
+pub(crate) fn invert(h: &Homography) -> Option<Homography> {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+        h[r0][c0] * h[r1][c1] - h[r0][c1] * h[r1][c0]
+    };
+    let c00 = cofactor(1, 2, 1, 2);
+    let c01 = -cofactor(1, 2, 0, 2);
+    let c02 = cofactor(1, 2, 0, 1);
+    let det = h[0][0] * c00 + h[0][1] * c01 + h[0][2] * c02;
+    if !det.is_finite() || det.abs() < f64::EPSILON {
+        return None;
+    }
+    // inverse = adjugate / det, where the adjugate is the transposed cofactor matrix
+    let adj = [
+        [c00, -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+        [c01, cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+        [c02, -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+    ];
+    let mut out = [[0.0; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            out[i][j] = adj[i][j] / det;
+        }
+    }
+    Some(out)
+}
+
+// The homography taking the tag's corners, (-1, 1), (1, 1), (1, -1) and (-1, -1), to `corners`.
+// Solved directly as eight linear equations with h[2][2] fixed at 1.
+#[cfg(feature = "3d")]
+pub(crate) fn from_corners(corners: &[Point; 4]) -> Option<Homography> {
+    const TAG: [Point; 4] = [[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+    let mut a = [[0.0; 9]; 8];
+    for (i, (t, c)) in TAG.iter().zip(corners).enumerate() {
+        let [x, y] = *t;
+        let [u, v] = *c;
+        a[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -x * u, -y * u, u];
+        a[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -x * v, -y * v, v];
+    }
+
+    // Gaussian elimination with partial pivoting
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        let pivot_row = a[col];
+        for (row, r) in a.iter_mut().enumerate() {
+            if row != col {
+                let factor = r[col] / pivot_row[col];
+                for (el, p) in r.iter_mut().zip(&pivot_row).skip(col) {
+                    *el -= factor * p;
+                }
+            }
+        }
+    }
+    let h: Vec<f64> = (0..8).map(|i| a[i][8] / a[i][i]).collect();
+    if !h.iter().all(|v| v.is_finite()) {
+        return None;
+    }
+    Some([
+        [h[0], h[1], h[2]],
+        [h[3], h[4], h[5]],
+        [h[6], h[7], 1.0],
+    ])
+}
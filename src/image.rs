@@ -1,9 +1,14 @@
 use crate::native::*;
+use crate::error::Error;
+use std::io::Write;
+use std::path::Path;
 
 #[allow(dead_code)]
 pub struct ImageU8<T: AsRef<[u8]>> {
     width: u32,
     height: u32,
+    stride: u32,
+    origin: (u32, u32),
     data: T,
 }
 
@@ -17,14 +22,47 @@ pub type Image<T> = ImageU8<T>;
 //     }
 // }
 
+// The number of bytes a buffer must hold for an image of the given shape. The last row doesn't
+// need to be padded out to the full stride.
+fn required_len(width: u32, height: u32, stride: u32) -> usize {
+    if width == 0 || height == 0 {
+        0
+    } else {
+        (height as usize - 1) * stride as usize + width as usize
+    }
+}
+
 #[allow(dead_code)]
 impl<T: AsRef<[u8]>> ImageU8<T> {
     pub fn new(width: u32, height: u32, data: T) -> ImageU8<T> {
-        ImageU8 {
+        ImageU8::with_stride(width, height, width, data)
+    }
+
+    pub fn try_new(width: u32, height: u32, data: T) -> Result<ImageU8<T>, Error> {
+        ImageU8::try_with_stride(width, height, width, data)
+    }
+
+    pub fn with_stride(width: u32, height: u32, stride: u32, data: T) -> ImageU8<T> {
+        ImageU8::try_with_stride(width, height, stride, data).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_with_stride(width: u32, height: u32, stride: u32, data: T) -> Result<ImageU8<T>, Error> {
+        // libapriltag stores all of these as int
+        if stride < width || stride > i32::MAX as u32 || height > i32::MAX as u32 {
+            return Err(Error::InvalidDimensions { width, height, stride });
+        }
+        let needed = required_len(width, height, stride);
+        let actual = data.as_ref().len();
+        if actual < needed {
+            return Err(Error::BufferTooSmall { needed, actual });
+        }
+        Ok(ImageU8 {
             width,
             height,
+            stride,
+            origin: (0, 0),
             data,
-        }
+        })
     }
     
     pub fn width(&self) -> u32 {
@@ -36,23 +74,309 @@ impl<T: AsRef<[u8]>> ImageU8<T> {
     }
 
     pub fn stride(&self) -> u32 {
-        self.width
+        self.stride
+    }
+
+    /// Position of this image's top-left pixel in the frame it was cut from. This is `(0, 0)`
+    /// for anything that isn't a view.
+    pub fn origin(&self) -> (u32, u32) {
+        self.origin
     }
 
     pub fn data(&self) -> &T {
         &self.data
     }
 
+    pub fn row(&self, y: u32) -> Option<&[u8]> {
+        if y >= self.height {
+            return None;
+        }
+        // An empty row may start past the end of the buffer, which only has to cover non-empty rows
+        if self.width == 0 {
+            return Some(&[]);
+        }
+        let start = y as usize * self.stride as usize;
+        Some(&self.data.as_ref()[start..start + self.width as usize])
+    }
+
+    /// Borrows the `w`x`h` region starting at `(x, y)` without copying. Detections found in a
+    /// view are reported in the coordinates of the full frame.
+    pub fn view(&self, x: u32, y: u32, w: u32, h: u32) -> ImageU8<&[u8]> {
+        self.try_view(x, y, w, h).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_view(&self, x: u32, y: u32, w: u32, h: u32) -> Result<ImageU8<&[u8]>, Error> {
+        if x as u64 + w as u64 > self.width as u64 || y as u64 + h as u64 > self.height as u64 {
+            return Err(Error::OutOfBounds { x, y, width: w, height: h });
+        }
+        let start = y as usize * self.stride as usize + x as usize;
+        let len = required_len(w, h, self.stride);
+        let data = if len == 0 {
+            &[][..]
+        } else {
+            &self.data.as_ref()[start..start + len]
+        };
+        Ok(ImageU8 {
+            width: w,
+            height: h,
+            stride: self.stride,
+            origin: (self.origin.0 + x, self.origin.1 + y),
+            data,
+        })
+    }
+
+    /// # Safety
+    /// The returned struct borrows this image's buffer, so it must not outlive `self`, and the C
+    /// side must not write through it.
     pub unsafe fn as_image_u8(&self) -> image_u8_t {
         image_u8_t {
             width: self.width as i32,
             height: self.height as i32,
-            stride: self.width as i32,
+            stride: self.stride as i32,
             buf: self.data.as_ref().as_ptr() as *mut u8,
         }
     }
 }
 
+// Converts packed pixels of `N` bytes each to gray, one row at a time. `stride` is in bytes.
+fn convert<const N: usize>(width: u32, height: u32, stride: u32, data: &[u8], luma: impl Fn(&[u8]) -> u8) -> Result<ImageU8<Vec<u8>>, Error> {
+    let row_len = width as usize * N;
+    if (stride as usize) < row_len {
+        return Err(Error::InvalidDimensions { width, height, stride });
+    }
+    // The row fits in the stride, so it fits in a u32 too
+    let needed = required_len(row_len as u32, height, stride);
+    if data.len() < needed {
+        return Err(Error::BufferTooSmall { needed, actual: data.len() });
+    }
+
+    let mut out = Vec::with_capacity(width as usize * height as usize);
+    if needed > 0 {
+        for row in data[..needed].chunks(stride as usize) {
+            out.extend(row[..row_len].chunks_exact(N).map(&luma));
+        }
+    }
+    ImageU8::try_new(width, height, out)
+}
+
+// ITU-R BT.601 luma in 8-bit fixed point, the same weights libapriltag's demos and OpenCV use.
+#[inline(always)]
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((77 * r as u32 + 150 * g as u32 + 29 * b as u32 + 128) >> 8) as u8
+}
+
+// Conversions from colour camera formats. These all copy into a new gray image, except `from_nv12`
+// which borrows the Y plane as it is. Strides are in bytes.
+impl ImageU8<Vec<u8>> {
+    pub fn from_rgb(width: u32, height: u32, stride: u32, data: &[u8]) -> Result<ImageU8<Vec<u8>>, Error> {
+        convert::<3>(width, height, stride, data, |p| luma(p[0], p[1], p[2]))
+    }
+
+    pub fn from_bgr(width: u32, height: u32, stride: u32, data: &[u8]) -> Result<ImageU8<Vec<u8>>, Error> {
+        convert::<3>(width, height, stride, data, |p| luma(p[2], p[1], p[0]))
+    }
+
+    /// Alpha is ignored.
+    pub fn from_rgba(width: u32, height: u32, stride: u32, data: &[u8]) -> Result<ImageU8<Vec<u8>>, Error> {
+        convert::<4>(width, height, stride, data, |p| luma(p[0], p[1], p[2]))
+    }
+
+    /// Alpha is ignored.
+    pub fn from_bgra(width: u32, height: u32, stride: u32, data: &[u8]) -> Result<ImageU8<Vec<u8>>, Error> {
+        convert::<4>(width, height, stride, data, |p| luma(p[2], p[1], p[0]))
+    }
+
+    /// Packed 4:2:2, as Y0 U Y1 V. `width` must be even.
+    pub fn from_yuyv(width: u32, height: u32, stride: u32, data: &[u8]) -> Result<ImageU8<Vec<u8>>, Error> {
+        if !width.is_multiple_of(2) {
+            return Err(Error::InvalidDimensions { width, height, stride });
+        }
+        convert::<2>(width, height, stride, data, |p| p[0])
+    }
+}
+
+impl<'a> ImageU8<&'a [u8]> {
+    /// Semi-planar 4:2:0: a full-resolution Y plane followed by interleaved U and V. The Y plane
+    /// already is a gray image, so it's borrowed without copying and the chroma is ignored.
+    pub fn from_nv12(width: u32, height: u32, stride: u32, data: &'a [u8]) -> Result<ImageU8<&'a [u8]>, Error> {
+        ImageU8::try_with_stride(width, height, stride, data)
+    }
+}
+
+// The whitespace-separated fields at the start of a PNM file, skipping `#` comments.
+struct PnmHeader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl PnmHeader<'_> {
+    fn skip_space(&mut self) {
+        while let Some(&c) = self.data.get(self.pos) {
+            if c == b'#' {
+                while self.data.get(self.pos).is_some_and(|&c| c != b'\n') {
+                    self.pos += 1;
+                }
+            } else if c.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self) -> &[u8] {
+        self.skip_space();
+        let start = self.pos;
+        while self.data.get(self.pos).is_some_and(|c| !c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn number(&mut self, what: &str) -> Result<u32, Error> {
+        std::str::from_utf8(self.token()).ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| Error::InvalidPnm(format!("missing or malformed {}", what)))
+    }
+}
+
+// Scales a sample from `0..=maxval` to `0..=255`.
+fn scale(value: u32, maxval: u32) -> Result<u8, Error> {
+    if value > maxval {
+        return Err(Error::InvalidPnm(format!("sample {} is larger than maxval {}", value, maxval)));
+    }
+    Ok(((value * 255 + maxval / 2) / maxval) as u8)
+}
+
+fn parse_pnm(data: &[u8]) -> Result<ImageU8<Vec<u8>>, Error> {
+    let mut header = PnmHeader { data, pos: 0 };
+    let magic = header.token().to_vec();
+    let channels = match magic.as_slice() {
+        b"P2" | b"P5" => 1,
+        b"P6" => 3,
+        _ => return Err(Error::InvalidPnm(format!("unsupported format {:?}", String::from_utf8_lossy(&magic)))),
+    };
+    let width = header.number("width")?;
+    let height = header.number("height")?;
+    let maxval = header.number("maxval")?;
+    if maxval == 0 || maxval > 65535 {
+        return Err(Error::InvalidPnm(format!("maxval {} is out of range", maxval)));
+    }
+    let too_large = || Error::InvalidPnm(format!("{}x{} image is too large", width, height));
+    let samples = (width as usize).checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(channels))
+        .ok_or_else(too_large)?;
+
+    let values: Vec<u8> = if magic == b"P2" {
+        // Every sample but the last takes at least two bytes, so a header can't make this
+        // allocate more than the file could fill
+        let remaining = data.len().saturating_sub(header.pos);
+        let mut values = Vec::with_capacity(samples.min(remaining / 2 + 1));
+        for _ in 0..samples {
+            values.push(scale(header.number("sample")?, maxval)?);
+        }
+        values
+    } else {
+        // A single whitespace byte separates the header from the raster
+        let start = header.pos + 1;
+        let bytes = if maxval > 255 { 2 } else { 1 };
+        let needed = samples.checked_mul(bytes).ok_or_else(too_large)?;
+        // Anything after the raster, like another image or a stray newline, is ignored
+        let raster = data.get(start..).unwrap_or(&[]);
+        let raster = raster.get(..needed).ok_or_else(|| {
+            Error::InvalidPnm(format!("raster is {} bytes, but {}x{} needs {}", raster.len(), width, height, needed))
+        })?;
+        if bytes == 1 && maxval == 255 {
+            raster.to_vec()
+        } else {
+            raster.chunks_exact(bytes)
+                .map(|b| scale(b.iter().fold(0, |v, &b| v << 8 | b as u32), maxval))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    if channels == 3 {
+        ImageU8::from_rgb(width, height, width.checked_mul(3).ok_or_else(too_large)?, &values)
+    } else {
+        ImageU8::try_new(width, height, values)
+    }
+}
+
+// PNM files, as used by libapriltag's demos and test images.
+impl ImageU8<Vec<u8>> {
+    /// Loads a binary (P5) or ASCII (P2) graymap, or a binary pixmap (P6) converted to gray.
+    /// Samples with a maxval other than 255 are rescaled to 8 bits. Only the first image in the
+    /// file is read.
+    pub fn load_pnm(path: impl AsRef<Path>) -> Result<ImageU8<Vec<u8>>, Error> {
+        parse_pnm(&std::fs::read(path)?)
+    }
+}
+
+impl<T: AsRef<[u8]>> ImageU8<T> {
+    /// Saves the image as a binary (P5) graymap.
+    pub fn save_pnm(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        write!(file, "P5\n{} {}\n255\n", self.width, self.height)?;
+        for y in 0..self.height {
+            file.write_all(self.row(y).expect("row is in bounds"))?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Borrows the image's pixels without copying.
+#[cfg(feature = "image")]
+impl<'a> From<&'a ::image::GrayImage> for ImageU8<&'a [u8]> {
+    fn from(image: &'a ::image::GrayImage) -> ImageU8<&'a [u8]> {
+        ImageU8::new(image.width(), image.height(), image.as_raw().as_slice())
+    }
+}
+
+/// Colour images are converted with the same weights as `ImageU8::from_rgb`.
+#[cfg(feature = "image")]
+impl TryFrom<&::image::DynamicImage> for ImageU8<Vec<u8>> {
+    type Error = Error;
+
+    fn try_from(image: &::image::DynamicImage) -> Result<ImageU8<Vec<u8>>, Error> {
+        use ::image::DynamicImage;
+        let (width, height) = (image.width(), image.height());
+        // Rows are packed, so the stride in bytes is the width times the bytes per pixel
+        let stride = |bytes: u32| width.checked_mul(bytes)
+            .ok_or(Error::InvalidDimensions { width, height, stride: u32::MAX });
+        match image {
+            DynamicImage::ImageLuma8(gray) => ImageU8::try_new(width, height, gray.as_raw().clone()),
+            DynamicImage::ImageRgb8(rgb) => ImageU8::from_rgb(width, height, stride(3)?, rgb.as_raw()),
+            DynamicImage::ImageRgba8(rgba) => ImageU8::from_rgba(width, height, stride(4)?, rgba.as_raw()),
+            other => ImageU8::from_rgb(width, height, stride(3)?, other.to_rgb8().as_raw()),
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl<T: AsRef<[u8]>> From<&ImageU8<T>> for ::image::GrayImage {
+    fn from(image: &ImageU8<T>) -> ::image::GrayImage {
+        let mut pixels = Vec::with_capacity(image.width as usize * image.height as usize);
+        for y in 0..image.height {
+            pixels.extend_from_slice(image.row(y).expect("row is in bounds"));
+        }
+        ::image::GrayImage::from_raw(image.width, image.height, pixels).expect("buffer matches dimensions")
+    }
+}
+
+/// Reuses the buffer when the image has no row padding.
+#[cfg(feature = "image")]
+impl From<ImageU8<Vec<u8>>> for ::image::GrayImage {
+    fn from(image: ImageU8<Vec<u8>>) -> ::image::GrayImage {
+        if image.stride != image.width {
+            return ::image::GrayImage::from(&image);
+        }
+        let mut pixels = image.data;
+        pixels.truncate(image.width as usize * image.height as usize);
+        ::image::GrayImage::from_raw(image.width, image.height, pixels).expect("buffer matches dimensions")
+    }
+}
+
 // impl Clone for ImageU8 {
 //     fn clone(&self) -> ImageU8 {
 //         unsafe {
@@ -2,13 +2,50 @@
 mod native;
 pub mod image;
 pub mod family;
+pub mod custom;
+pub mod generator;
 // mod array;
 pub mod detector;
+pub mod print;
+#[cfg(feature = "3d")]
+pub mod board;
+#[cfg(feature = "3d")]
+pub mod field;
+#[cfg(feature = "3d")]
+pub mod distortion;
+#[cfg(feature = "3d")]
+pub mod frames;
+#[cfg(feature = "3d")]
+pub mod covariance;
+#[cfg(feature = "3d")]
+pub mod refine;
+#[cfg(feature = "3d")]
+pub mod calibration;
+mod error;
+mod homography;
+#[cfg(feature = "3d")]
+mod pnp;
 
 pub use image::{ImageU8, Image};
-pub use family::TagFamily;
-pub use detector::{Detector};
+pub use family::{TagFamily, RenderOptions};
+pub use custom::CustomFamily;
+pub use detector::{Detector, DetectorConfig, QuadThresholdParams, Detection, TagDetection};
+pub use error::Error;
 
 #[cfg(feature = "3d")]
-pub use detector::{CameraIntrinsics};
+pub use detector::{CameraIntrinsics, Pose, PoseEstimate};
+#[cfg(feature = "3d")]
+pub use distortion::{Distortion, undistort_points};
+#[cfg(feature = "3d")]
+pub use frames::Frame;
+#[cfg(feature = "3d")]
+pub use covariance::{Covariance, CornerNoise, PoseWithCovariance};
+#[cfg(feature = "3d")]
+pub use refine::{Loss, RefineOptions, RefineStatus, RefinedPose};
+#[cfg(feature = "3d")]
+pub use board::{TagBoard, BoardPose};
+#[cfg(feature = "3d")]
+pub use field::{FieldLayout, CameraPose, Localization};
+#[cfg(feature = "3d")]
+pub use calibration::{Calibrator, Calibration, DistortionModel};
 
@@ -0,0 +1,159 @@
+// Small pure-Rust pieces of perspective-n-point, for the solvers that libapriltag doesn't cover.
+use crate::detector::{CameraIntrinsics, Point};
+use crate::refine::{Loss, RefineOptions};
+use nalgebra::{Matrix3, Matrix6, Rotation3, Vector3, Vector6};
+
+/// Corners of a tag in its own frame, in units of half the tag size and in the same order as
+/// `TagDetection::corners`. The tag frame has x to the right, y down and z into the tag, as in
+/// libapriltag's pose estimate.
+pub(crate) const TAG_CORNERS: [[f64; 2]; 4] = [[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+
+pub(crate) fn tag_corners(tag_size: f64) -> Vec<Vector3<f64>> {
+    TAG_CORNERS.iter().map(|[x, y]| Vector3::new(x * tag_size / 2.0, y * tag_size / 2.0, 0.0)).collect()
+}
+
+// A pose as a camera-from-object rotation and translation.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Solution {
+    pub rotation: Matrix3<f64>,
+    pub translation: Vector3<f64>,
+    /// Root-mean-square reprojection error, in pixels.
+    pub rms: f64,
+    pub cost: f64,
+    pub iterations: u32,
+    pub converged: bool,
+}
+
+pub(crate) fn project(intrinsics: &CameraIntrinsics, camera_point: &Vector3<f64>) -> Option<Point> {
+    if camera_point.z <= f64::EPSILON {
+        return None;
+    }
+    Some([
+        intrinsics.fx * camera_point.x / camera_point.z + intrinsics.cx,
+        intrinsics.fy * camera_point.y / camera_point.z + intrinsics.cy,
+    ])
+}
+
+// Robust cost and squared reprojection error, or None if any point ends up behind the camera.
+fn cost(object: &[Vector3<f64>], image: &[Point], intrinsics: &CameraIntrinsics, loss: Loss, rotation: &Matrix3<f64>, translation: &Vector3<f64>) -> Option<(f64, f64)> {
+    let mut robust = 0.0;
+    let mut squared = 0.0;
+    for (x, observed) in object.iter().zip(image) {
+        let p = project(intrinsics, &(rotation * x + translation))?;
+        let s = (p[0] - observed[0]).powi(2) + (p[1] - observed[1]).powi(2);
+        robust += loss.rho(s);
+        squared += s;
+    }
+    Some((robust, squared))
+}
+
+/// Derivatives of the projection of `x` with respect to a rotation `w` applied on the left and a
+/// translation added after, in that order, along with the projection itself.
+pub(crate) fn jacobian(intrinsics: &CameraIntrinsics, rotation: &Matrix3<f64>, translation: &Vector3<f64>, x: &Vector3<f64>) -> ([Vector6<f64>; 2], Point) {
+    let rotated = rotation * x;
+    let c = rotated + translation;
+    let (iz, iz2) = (1.0 / c.z, 1.0 / (c.z * c.z));
+    let du = Vector3::new(intrinsics.fx * iz, 0.0, -intrinsics.fx * c.x * iz2);
+    let dv = Vector3::new(0.0, intrinsics.fy * iz, -intrinsics.fy * c.y * iz2);
+    // d(exp(w) * r)/dw at w = 0 is -[r]x, so d/dw of a row vector g is r x g
+    let row = |g: Vector3<f64>| {
+        let dw = rotated.cross(&g);
+        Vector6::new(dw.x, dw.y, dw.z, g.x, g.y, g.z)
+    };
+    (
+        [row(du), row(dv)],
+        [intrinsics.fx * c.x * iz + intrinsics.cx, intrinsics.fy * c.y * iz + intrinsics.cy],
+    )
+}
+
+/// Levenberg-Marquardt on reprojection error, starting from `rotation` and `translation`.
+///
+/// Rotation updates are applied on the left, as `exp(w) * R`, so the parameters never leave SO(3).
+/// Robust losses are handled by reweighting each corner by the loss's slope at its current error.
+pub(crate) fn refine(object: &[Vector3<f64>], image: &[Point], intrinsics: &CameraIntrinsics, rotation: Matrix3<f64>, translation: Vector3<f64>, options: &RefineOptions) -> Option<Solution> {
+    let loss = options.loss;
+    let mut rotation = rotation;
+    let mut translation = translation;
+    let (mut current, mut squared) = cost(object, image, intrinsics, loss, &rotation, &translation)?;
+    let mut lambda = 1e-3;
+    let mut iterations = 0;
+    let mut converged = false;
+
+    while iterations < options.max_iterations {
+        iterations += 1;
+        let mut jtj = Matrix6::zeros();
+        let mut jtr = Vector6::zeros();
+        for (x, observed) in object.iter().zip(image) {
+            let (rows, projected) = jacobian(intrinsics, &rotation, &translation, x);
+            let r = [projected[0] - observed[0], projected[1] - observed[1]];
+            let weight = loss.weight(r[0] * r[0] + r[1] * r[1]);
+            for (j, r) in rows.iter().zip(r) {
+                jtj += j * j.transpose() * weight;
+                jtr += j * r * weight;
+            }
+        }
+
+        let mut improved = false;
+        while lambda < 1e10 {
+            let mut damped = jtj;
+            for i in 0..6 {
+                damped[(i, i)] += lambda * jtj[(i, i)].max(1e-12);
+            }
+            let Some(step) = damped.cholesky().map(|c| c.solve(&(-jtr))) else {
+                lambda *= 10.0;
+                continue;
+            };
+            let next_rotation = Rotation3::new(Vector3::new(step[0], step[1], step[2])).into_inner() * rotation;
+            let next_translation = translation + Vector3::new(step[3], step[4], step[5]);
+            match cost(object, image, intrinsics, loss, &next_rotation, &next_translation) {
+                Some((next, next_squared)) if next <= current => {
+                    converged = current - next <= options.tolerance * current.max(1.0) || step.norm() < 1e-12;
+                    rotation = next_rotation;
+                    translation = next_translation;
+                    current = next;
+                    squared = next_squared;
+                    lambda = (lambda / 10.0).max(1e-12);
+                    improved = true;
+                    break;
+                }
+                _ => lambda *= 10.0,
+            }
+        }
+        // No step downhill, however small, means we're already at the bottom
+        if !improved {
+            converged = true;
+        }
+        if converged {
+            break;
+        }
+    }
+
+    Some(Solution {
+        rotation,
+        translation,
+        rms: (squared / object.len() as f64).sqrt(),
+        cost: current,
+        iterations,
+        converged,
+    })
+}
+
+/// The rigid transform taking `from` onto `to` with the least squared error (Kabsch).
+pub(crate) fn fit_rigid(from: &[Vector3<f64>], to: &[Vector3<f64>]) -> Option<(Matrix3<f64>, Vector3<f64>)> {
+    let n = from.len() as f64;
+    let from_mean = from.iter().sum::<Vector3<f64>>() / n;
+    let to_mean = to.iter().sum::<Vector3<f64>>() / n;
+    let mut h = Matrix3::zeros();
+    for (a, b) in from.iter().zip(to) {
+        h += (a - from_mean) * (b - to_mean).transpose();
+    }
+    let svd = h.svd(true, true);
+    let u = svd.u?;
+    let v = svd.v_t?.transpose();
+    let mut d = Matrix3::identity();
+    if (v * u.transpose()).determinant() < 0.0 {
+        d[(2, 2)] = -1.0;
+    }
+    let rotation = v * d * u.transpose();
+    Some((rotation, to_mean - rotation * from_mean))
+}
@@ -0,0 +1,308 @@
+use crate::error::Error;
+use crate::family::TagFamily;
+
+use std::fmt::Write;
+
+const MM_TO_PT: f64 = 72.0 / 25.4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PageSize {
+    A4,
+    Letter,
+    Custom { width_mm: f64, height_mm: f64 },
+}
+
+impl PageSize {
+    pub fn dimensions_mm(&self) -> (f64, f64) {
+        match *self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::Letter => (215.9, 279.4),
+            PageSize::Custom { width_mm, height_mm } => (width_mm, height_mm),
+        }
+    }
+}
+
+/// A set of tags laid out on one or more pages at an exact physical size.
+///
+/// `tag_size_mm` is measured the same way as the `tag_size` passed to pose estimation: across the
+/// square whose corners the detector reports, not across the whole printed pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagSheet {
+    pub family: TagFamily,
+    pub ids: Vec<u32>,
+    pub tag_size_mm: f64,
+    pub page: PageSize,
+    pub margin_mm: f64,
+    pub spacing_mm: f64,
+    /// White cells left around the pattern, inside the crop marks.
+    pub quiet_zone_cells: u32,
+    pub labels: bool,
+    pub crop_marks: bool,
+}
+
+// Everything is laid out in millimetres with the origin at the top-left corner of the page. The
+// PDF writer flips it around when converting to points.
+enum Shape {
+    Rect { x: f64, y: f64, w: f64, h: f64 },
+    Line { x1: f64, y1: f64, x2: f64, y2: f64 },
+    Text { x: f64, y: f64, size: f64, text: String },
+}
+
+// (id, x, y) for each tag on a page
+type PageLayout = Vec<(u32, f64, f64)>;
+
+const LABEL_SIZE_MM: f64 = 3.0;
+const LABEL_GAP_MM: f64 = 1.5;
+const CROP_MARK_GAP_MM: f64 = 1.0;
+const CROP_MARK_LEN_MM: f64 = 4.0;
+const LINE_WIDTH_MM: f64 = 0.1;
+
+impl TagSheet {
+    pub fn new(family: TagFamily, ids: Vec<u32>, tag_size_mm: f64, page: PageSize) -> TagSheet {
+        TagSheet {
+            family,
+            ids,
+            tag_size_mm,
+            page,
+            margin_mm: 10.0,
+            spacing_mm: 2.0 * (CROP_MARK_GAP_MM + CROP_MARK_LEN_MM),
+            quiet_zone_cells: 1,
+            labels: true,
+            crop_marks: true,
+        }
+    }
+
+    fn cell_mm(&self) -> f64 {
+        self.tag_size_mm / self.family.width_at_border() as f64
+    }
+
+    // Side of the area inside the crop marks: the whole pattern plus its quiet zone.
+    fn crop_mm(&self) -> f64 {
+        (self.family.total_width() + 2 * self.quiet_zone_cells) as f64 * self.cell_mm()
+    }
+
+    // How far the crop marks reach below the crop area.
+    fn crop_marks_mm(&self) -> f64 {
+        if self.crop_marks {CROP_MARK_GAP_MM + CROP_MARK_LEN_MM} else {0.0}
+    }
+
+    // Labels go below the crop marks, so that the marks never run into the text.
+    fn label_mm(&self) -> f64 {
+        if self.labels {self.crop_marks_mm() + LABEL_GAP_MM + LABEL_SIZE_MM} else {0.0}
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if !(self.tag_size_mm.is_finite() && self.tag_size_mm > 0.0) {
+            return Err(Error::InvalidConfig { field: "tag_size_mm", reason: "must be positive" });
+        }
+        let (width, height) = self.page.dimensions_mm();
+        if !(width.is_finite() && height.is_finite() && width > 0.0 && height > 0.0) {
+            return Err(Error::InvalidConfig { field: "page", reason: "dimensions must be positive" });
+        }
+        if !(self.margin_mm.is_finite() && self.margin_mm >= 0.0) {
+            return Err(Error::InvalidConfig { field: "margin_mm", reason: "must be non-negative" });
+        }
+        if !(self.spacing_mm.is_finite() && self.spacing_mm >= 0.0) {
+            return Err(Error::InvalidConfig { field: "spacing_mm", reason: "must be non-negative" });
+        }
+        if self.ids.is_empty() {
+            return Err(Error::InvalidConfig { field: "ids", reason: "must not be empty" });
+        }
+        let count = self.family.code_count();
+        if let Some(&id) = self.ids.iter().find(|&&id| id >= count) {
+            return Err(Error::InvalidId { id, count });
+        }
+        Ok(())
+    }
+
+    // Top-left corners of the crop area of every tag, grouped by page.
+    fn layout(&self) -> Result<Vec<PageLayout>, Error> {
+        self.validate()?;
+        let (width, height) = self.page.dimensions_mm();
+        let slot_w = self.crop_mm();
+        let slot_h = slot_w + self.label_mm();
+        let fit = |space: f64, slot: f64| {
+            ((space - 2.0 * self.margin_mm + self.spacing_mm) / (slot + self.spacing_mm)).floor().max(0.0) as usize
+        };
+        let cols = fit(width, slot_w);
+        let rows = fit(height, slot_h);
+        if cols == 0 || rows == 0 {
+            return Err(Error::InvalidConfig { field: "tag_size_mm", reason: "tag doesn't fit on the page" });
+        }
+
+        // Center the grid on the page
+        let x0 = (width - (cols as f64 * (slot_w + self.spacing_mm) - self.spacing_mm)) / 2.0;
+        let y0 = (height - (rows as f64 * (slot_h + self.spacing_mm) - self.spacing_mm)) / 2.0;
+        Ok(self.ids.chunks(cols * rows).map(|page| {
+            page.iter().enumerate().map(|(i, &id)| {
+                let col = i % cols;
+                let row = i / cols;
+                (id, x0 + col as f64 * (slot_w + self.spacing_mm), y0 + row as f64 * (slot_h + self.spacing_mm))
+            }).collect()
+        }).collect())
+    }
+
+    fn tag_shapes(&self, id: u32, x: f64, y: f64, shapes: &mut Vec<Shape>) -> Result<(), Error> {
+        let cells = self.family.pattern(id)?;
+        let total = self.family.total_width() as usize;
+        let cell = self.cell_mm();
+        let origin_x = x + self.quiet_zone_cells as f64 * cell;
+        let origin_y = y + self.quiet_zone_cells as f64 * cell;
+
+        // One rectangle per horizontal run of black cells keeps the output small
+        for (row, line) in cells.chunks_exact(total).enumerate() {
+            let mut col = 0;
+            while col < total {
+                if line[col] {
+                    col += 1;
+                    continue;
+                }
+                let start = col;
+                while col < total && !line[col] {
+                    col += 1;
+                }
+                shapes.push(Shape::Rect {
+                    x: origin_x + start as f64 * cell,
+                    y: origin_y + row as f64 * cell,
+                    w: (col - start) as f64 * cell,
+                    h: cell,
+                });
+            }
+        }
+
+        let side = self.crop_mm();
+        if self.crop_marks {
+            let near = CROP_MARK_GAP_MM;
+            let far = CROP_MARK_GAP_MM + CROP_MARK_LEN_MM;
+            for (cx, dx) in [(x, -1.0), (x + side, 1.0)] {
+                for (cy, dy) in [(y, -1.0), (y + side, 1.0)] {
+                    shapes.push(Shape::Line { x1: cx + dx * near, y1: cy, x2: cx + dx * far, y2: cy });
+                    shapes.push(Shape::Line { x1: cx, y1: cy + dy * near, x2: cx, y2: cy + dy * far });
+                }
+            }
+        }
+        if self.labels {
+            shapes.push(Shape::Text {
+                x,
+                y: y + side + self.label_mm(),
+                size: LABEL_SIZE_MM,
+                text: format!("{} id {} ({} mm)", self.family, id, self.tag_size_mm),
+            });
+        }
+        Ok(())
+    }
+
+    fn pages(&self) -> Result<Vec<Vec<Shape>>, Error> {
+        self.layout()?.into_iter().map(|page| {
+            let mut shapes = Vec::new();
+            for (id, x, y) in page {
+                self.tag_shapes(id, x, y, &mut shapes)?;
+            }
+            Ok(shapes)
+        }).collect()
+    }
+
+    /// One SVG document per page, sized in millimetres.
+    pub fn to_svg(&self) -> Result<Vec<String>, Error> {
+        let (width, height) = self.page.dimensions_mm();
+        Ok(self.pages()?.into_iter().map(|shapes| {
+            let mut out = String::new();
+            let _ = writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+            let _ = writeln!(out,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="0 0 {w} {h}">"#,
+                w = width, h = height);
+            let _ = writeln!(out, r#"<rect x="0" y="0" width="{}" height="{}" fill="white"/>"#, width, height);
+            let _ = writeln!(out, r#"<g fill="black" stroke="none" shape-rendering="crispEdges">"#);
+            for shape in &shapes {
+                if let Shape::Rect { x, y, w, h } = shape {
+                    let _ = writeln!(out, r#"<rect x="{:.4}" y="{:.4}" width="{:.4}" height="{:.4}"/>"#, x, y, w, h);
+                }
+            }
+            let _ = writeln!(out, "</g>");
+            let _ = writeln!(out, r#"<g stroke="black" stroke-width="{}">"#, LINE_WIDTH_MM);
+            for shape in &shapes {
+                if let Shape::Line { x1, y1, x2, y2 } = shape {
+                    let _ = writeln!(out, r#"<line x1="{:.4}" y1="{:.4}" x2="{:.4}" y2="{:.4}"/>"#, x1, y1, x2, y2);
+                }
+            }
+            let _ = writeln!(out, "</g>");
+            for shape in &shapes {
+                if let Shape::Text { x, y, size, text } = shape {
+                    let _ = writeln!(out, r#"<text x="{:.4}" y="{:.4}" font-family="Helvetica, Arial, sans-serif" font-size="{}">{}</text>"#,
+                        x, y, size, escape_xml(text));
+                }
+            }
+            out.push_str("</svg>\n");
+            out
+        }).collect())
+    }
+
+    /// A complete PDF document with one page per sheet. Labels use the built-in Helvetica font, so
+    /// nothing needs to be embedded.
+    pub fn to_pdf(&self) -> Result<Vec<u8>, Error> {
+        let (width, height) = self.page.dimensions_mm();
+        let pages = self.pages()?;
+
+        // Objects 1-3 are the catalog, page tree and font, then each page is followed by its
+        // content stream.
+        let mut objects = Vec::new();
+        let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", 4 + 2 * i)).collect();
+        objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+        objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()));
+        objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string());
+
+        let pt = |mm: f64| mm * MM_TO_PT;
+        for (i, shapes) in pages.iter().enumerate() {
+            let mut content = String::new();
+            content.push_str("0 g\n");
+            for shape in shapes {
+                if let Shape::Rect { x, y, w, h } = shape {
+                    let _ = writeln!(content, "{:.3} {:.3} {:.3} {:.3} re", pt(*x), pt(height - y - h), pt(*w), pt(*h));
+                }
+            }
+            content.push_str("f\n");
+            let _ = writeln!(content, "0 G {:.3} w", pt(LINE_WIDTH_MM));
+            for shape in shapes {
+                if let Shape::Line { x1, y1, x2, y2 } = shape {
+                    let _ = writeln!(content, "{:.3} {:.3} m {:.3} {:.3} l", pt(*x1), pt(height - y1), pt(*x2), pt(height - y2));
+                }
+            }
+            content.push_str("S\n");
+            for shape in shapes {
+                if let Shape::Text { x, y, size, text } = shape {
+                    let _ = writeln!(content, "BT /F1 {:.3} Tf {:.3} {:.3} Td ({}) Tj ET",
+                        pt(*size), pt(*x), pt(height - y), escape_pdf(text));
+                }
+            }
+
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.3} {:.3}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                pt(width), pt(height), 5 + 2 * i));
+            objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"%PDF-1.4\n");
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, obj) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, obj).as_bytes());
+        }
+        let xref = out.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(trailer, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref);
+        out.extend_from_slice(trailer.as_bytes());
+        Ok(out)
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn escape_pdf(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('(', "\\(").replace(')', "\\)")
+}
@@ -0,0 +1,130 @@
+use crate::detector::{CameraIntrinsics, Detection, Pose, Rotation, TagDetection, Translation};
+use crate::error::Error;
+use crate::pnp;
+use nalgebra::Vector3;
+
+/// How reprojection errors are penalized. The scales are in pixels: corners further off than
+/// that count for less than they would under `Squared`, so a single bad corner can't drag the
+/// whole pose. With only a tag's four corners to go on, `Huber` still lets a large outlier pull
+/// on the pose, while `Cauchy` all but ignores it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Loss {
+    Squared,
+    Huber(f64),
+    Cauchy(f64),
+}
+
+impl Loss {
+    // The loss of a squared error `s`
+    pub(crate) fn rho(self, s: f64) -> f64 {
+        match self {
+            Loss::Squared => s,
+            Loss::Huber(d) if s <= d * d => s,
+            Loss::Huber(d) => 2.0 * d * s.sqrt() - d * d,
+            Loss::Cauchy(d) => d * d * (s / (d * d)).ln_1p(),
+        }
+    }
+
+    // d(rho)/ds, which is the weight of the corner in each Gauss-Newton step
+    pub(crate) fn weight(self, s: f64) -> f64 {
+        match self {
+            Loss::Squared => 1.0,
+            Loss::Huber(d) if s <= d * d => 1.0,
+            Loss::Huber(d) => d / s.sqrt(),
+            Loss::Cauchy(d) => 1.0 / (1.0 + s / (d * d)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RefineOptions {
+    pub max_iterations: u32,
+    pub loss: Loss,
+    /// Stop once an iteration improves the cost by less than this fraction.
+    pub tolerance: f64,
+}
+
+impl Default for RefineOptions {
+    fn default() -> RefineOptions {
+        RefineOptions {
+            max_iterations: 100,
+            loss: Loss::Squared,
+            tolerance: 1e-12,
+        }
+    }
+}
+
+impl RefineOptions {
+    pub fn validate(&self) -> Result<(), Error> {
+        match self.loss {
+            Loss::Huber(d) | Loss::Cauchy(d) if !(d.is_finite() && d > 0.0) => {
+                Err(Error::InvalidConfig { field: "loss", reason: "scale must be positive" })
+            }
+            _ if self.tolerance.is_nan() || self.tolerance < 0.0 => {
+                Err(Error::InvalidConfig { field: "tolerance", reason: "must not be negative" })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RefineStatus {
+    /// The cost stopped improving.
+    Converged,
+    /// `max_iterations` ran out first. The pose is still the best one found.
+    MaxIterations,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RefinedPose {
+    pub pose: Pose,
+    pub status: RefineStatus,
+    pub iterations: u32,
+    /// Root-mean-square reprojection error over the four corners, in pixels, regardless of loss.
+    pub rms: f64,
+    /// Final value of the loss, summed over the corners.
+    pub cost: f64,
+}
+
+impl TagDetection {
+    /// Refines `initial` by minimizing the reprojection error of the tag's corners in Rust, rather
+    /// than in libapriltag. `initial` usually comes from `estimate_pose`, and should at least put
+    /// the tag in front of the camera.
+    pub fn refine_pose(&self, intrinsics: &CameraIntrinsics, tag_size: f64, initial: &Pose, options: &RefineOptions) -> Result<RefinedPose, Error> {
+        options.validate()?;
+        if !(tag_size.is_finite() && tag_size > 0.0) {
+            return Err(Error::InvalidConfig { field: "tag_size", reason: "must be positive" });
+        }
+        let det = self.undistorted(intrinsics)?;
+        let solution = pnp::refine(
+            &pnp::tag_corners(tag_size),
+            &det.corners,
+            &intrinsics.pinhole(),
+            initial.rot.matrix(),
+            Vector3::from(initial.pos),
+            options,
+        ).ok_or(Error::DegeneratePose("the initial pose puts the tag behind the camera"))?;
+
+        Ok(RefinedPose {
+            pose: Pose {
+                rot: Rotation::from_matrix(solution.rotation)?,
+                pos: Translation::from(solution.translation),
+            },
+            status: if solution.converged {RefineStatus::Converged} else {RefineStatus::MaxIterations},
+            iterations: solution.iterations,
+            rms: solution.rms,
+            cost: solution.cost,
+        })
+    }
+}
+
+impl Detection {
+    pub fn refine_pose(&self, intrinsics: &CameraIntrinsics, tag_size: f64, initial: &Pose, options: &RefineOptions) -> Result<RefinedPose, Error> {
+        TagDetection::try_from(self)?.refine_pose(intrinsics, tag_size, initial, options)
+    }
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "3d")]
+mod common;
+
+use apriltag_rs::detector::{Rotation, Translation};
+use apriltag_rs::{CameraIntrinsics, Error, Pose, TagBoard, TagFamily};
+use common::{assert_pose_close, board_detections, detection};
+
+fn camera() -> CameraIntrinsics {
+    CameraIntrinsics::new(600.0, 600.0, 320.0, 240.0)
+}
+
+#[test]
+fn grid_pose_is_recovered() {
+    let board = TagBoard::grid(TagFamily::Tag36h11, 2, 3, 0.1, 0.02, 10);
+    let truth = Pose {
+        rot: Rotation::from_roll_pitch_yaw(0.2, -0.3, 0.1),
+        pos: Translation { x: -0.15, y: -0.05, z: 0.9 },
+    };
+    let detections = board_detections(&board, &camera(), &truth);
+    let estimate = board.estimate_pose(&detections, &camera()).unwrap();
+    assert_pose_close(&estimate.pose, &truth, 1e-6);
+    assert!(estimate.error < 1e-6);
+    assert_eq!(estimate.ids, (10..16).collect::<Vec<_>>());
+}
+
+#[test]
+fn tags_off_the_board_plane_are_placed_by_pose() {
+    // Two faces of a box meeting at a right angle, which a planar solver can't handle
+    let mut board = TagBoard::new(TagFamily::Tag25h9);
+    let face = Rotation::from_axis_angle([0.0, 1.0, 0.0], std::f64::consts::FRAC_PI_2).unwrap();
+    board.add_tag_with_pose(1, &Pose { pos: Translation { x: 0.1, y: 0.0, z: 0.0 }, ..Pose::identity() }, 0.08);
+    board.add_tag_with_pose(2, &Pose { rot: face, pos: Translation { x: 0.0, y: 0.0, z: -0.1 } }, 0.08);
+    board.add_tag_with_pose(3, &Pose { rot: face, pos: Translation { x: 0.0, y: 0.1, z: -0.1 } }, 0.08);
+
+    let truth = Pose {
+        rot: Rotation::from_axis_angle([0.2, -1.0, 0.1], 0.8).unwrap(),
+        pos: Translation { x: 0.02, y: -0.04, z: 0.7 },
+    };
+    let mut detections = board_detections(&board, &camera(), &truth);
+    // Tags from another family or not on the board are ignored
+    let stray = detections[0].corners;
+    detections.push(detection(TagFamily::Tag36h11, 1, stray));
+    detections.push(detection(TagFamily::Tag25h9, 99, stray));
+
+    let estimate = board.estimate_pose(&detections, &camera()).unwrap();
+    assert_pose_close(&estimate.pose, &truth, 1e-6);
+    assert_eq!(estimate.ids, vec![1, 2, 3]);
+}
+
+#[test]
+fn needs_a_detection_on_the_board() {
+    let board = TagBoard::grid(TagFamily::Tag36h11, 1, 2, 0.1, 0.02, 0);
+    let other = detection(TagFamily::Tag36h11, 5, [[0.0, 10.0], [10.0, 10.0], [10.0, 0.0], [0.0, 0.0]]);
+    assert!(board.estimate_pose(&[other], &camera()).is_err());
+    assert!(board.estimate_pose(&[], &camera()).is_err());
+}
+
+#[test]
+fn grid_ids_must_fit() {
+    let board = TagBoard::try_grid(TagFamily::Tag36h11, 2, 2, 0.1, 0.02, u32::MAX - 3).unwrap();
+    assert_eq!(board.ids().last(), Some(u32::MAX));
+    assert!(matches!(
+        TagBoard::try_grid(TagFamily::Tag36h11, 2, 2, 0.1, 0.02, u32::MAX - 2),
+        Err(Error::InvalidConfig { field: "first_id", .. })
+    ));
+    assert!(TagBoard::try_grid(TagFamily::Tag36h11, 1 << 16, 1 << 16, 0.1, 0.02, 0).is_err());
+    assert!(TagBoard::try_grid(TagFamily::Tag36h11, 0, 5, 0.1, 0.02, u32::MAX).unwrap().is_empty());
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "3d")]
+mod common;
+
+use apriltag_rs::detector::{Rotation, Translation};
+use apriltag_rs::{CameraIntrinsics, Calibrator, Distortion, DistortionModel, Pose, TagBoard, TagFamily};
+use common::{assert_pose_close, board_detections};
+
+fn board(unit: f64) -> TagBoard {
+    TagBoard::grid(TagFamily::Tag36h11, 3, 4, 0.05 * unit, 0.01 * unit, 0)
+}
+
+fn views(unit: f64) -> Vec<Pose> {
+    [(0.3, -0.2, 0.1, 0.5), (-0.35, 0.1, -0.2, 0.45), (0.1, 0.4, 0.3, 0.55), (-0.2, -0.35, 0.0, 0.4), (0.25, 0.25, -0.4, 0.6)]
+        .into_iter()
+        .map(|(roll, pitch, yaw, z)| Pose {
+            rot: Rotation::from_roll_pitch_yaw(roll, pitch, yaw),
+            pos: Translation { x: -0.12 * unit, y: -0.08 * unit, z: z * unit },
+        })
+        .collect()
+}
+
+fn calibrate(camera: &CameraIntrinsics, model: DistortionModel, unit: f64) -> apriltag_rs::Calibration {
+    let board = board(unit);
+    let poses = views(unit);
+    let frames: Vec<_> = poses.iter().map(|pose| board_detections(&board, camera, pose)).collect();
+    let mut calibrator = Calibrator::new(board);
+    calibrator.model = model;
+    let calibration = calibrator.calibrate_detections(&frames).unwrap();
+
+    assert!(calibration.rms < 1e-6, "rms {}", calibration.rms);
+    assert_eq!(calibration.frames.len(), poses.len());
+    for (frame, pose) in calibration.frames.iter().zip(&poses) {
+        let scaled = Pose { pos: Translation { x: pose.pos.x / unit, y: pose.pos.y / unit, z: pose.pos.z / unit }, ..*pose };
+        let found = Pose { pos: Translation { x: frame.pose.pos.x / unit, y: frame.pose.pos.y / unit, z: frame.pose.pos.z / unit }, ..frame.pose };
+        assert_pose_close(&found, &scaled, 1e-5);
+    }
+    calibration
+}
+
+fn assert_camera_close(actual: &CameraIntrinsics, expected: &CameraIntrinsics) {
+    let close = |a: f64, b: f64, tolerance: f64| (a - b).abs() < tolerance;
+    assert!(
+        close(actual.fx, expected.fx, 1e-3) && close(actual.fy, expected.fy, 1e-3)
+            && close(actual.cx, expected.cx, 1e-3) && close(actual.cy, expected.cy, 1e-3),
+        "{:?} != {:?}", actual, expected
+    );
+    let coefficients = |d: Distortion| match d {
+        Distortion::None => vec![],
+        Distortion::BrownConrady { k1, k2, k3, p1, p2 } => vec![k1, k2, k3, p1, p2],
+        Distortion::KannalaBrandt { k1, k2, k3, k4 } => vec![k1, k2, k3, k4],
+    };
+    let (a, e) = (coefficients(actual.distortion), coefficients(expected.distortion));
+    assert_eq!(a.len(), e.len());
+    assert!(a.iter().zip(&e).all(|(a, e)| close(*a, *e, 1e-4)), "{:?} != {:?}", a, e);
+}
+
+#[test]
+fn brown_conrady_cameras_are_recovered() {
+    let camera = CameraIntrinsics::new(800.0, 790.0, 330.0, 250.0)
+        .with_distortion(Distortion::BrownConrady { k1: -0.2, k2: 0.05, k3: 0.0, p1: 1e-3, p2: -5e-4 });
+    let calibration = calibrate(&camera, DistortionModel::BrownConrady, 1.0);
+    assert_camera_close(&calibration.intrinsics, &camera);
+}
+
+#[test]
+fn kannala_brandt_cameras_are_recovered() {
+    let camera = CameraIntrinsics::new(400.0, 400.0, 320.0, 240.0)
+        .with_distortion(Distortion::KannalaBrandt { k1: 0.02, k2: -0.01, k3: 0.003, k4: -5e-4 });
+    let calibration = calibrate(&camera, DistortionModel::KannalaBrandt, 1.0);
+    assert_camera_close(&calibration.intrinsics, &camera);
+}
+
+#[test]
+fn board_units_dont_matter() {
+    let camera = CameraIntrinsics::new(700.0, 705.0, 310.0, 245.0);
+    let metres = calibrate(&camera, DistortionModel::None, 1.0);
+    let millimetres = calibrate(&camera, DistortionModel::None, 1000.0);
+    assert_camera_close(&metres.intrinsics, &camera);
+    assert_camera_close(&millimetres.intrinsics, &camera);
+}
@@ -0,0 +1,57 @@
+// Synthetic detections, for testing the 3D code without rendering and detecting real images.
+#![allow(dead_code)]
+use apriltag_rs::detector::{Homography, Point};
+use apriltag_rs::{CameraIntrinsics, Pose, TagBoard, TagDetection, TagFamily};
+use nalgebra::{SMatrix, SVector};
+
+// Corners in tag coordinates, in the order libapriltag reports them.
+const TAG_CORNERS: [Point; 4] = [[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+
+/// The homography taking the tag's corners in tag coordinates to `corners`.
+pub fn homography(corners: &[Point; 4]) -> Homography {
+    let mut a = SMatrix::<f64, 8, 8>::zeros();
+    let mut b = SVector::<f64, 8>::zeros();
+    for (i, ([x, y], [u, v])) in TAG_CORNERS.iter().zip(corners).enumerate() {
+        a.row_mut(2 * i).copy_from_slice(&[*x, *y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y]);
+        a.row_mut(2 * i + 1).copy_from_slice(&[0.0, 0.0, 0.0, *x, *y, 1.0, -v * x, -v * y]);
+        b[2 * i] = *u;
+        b[2 * i + 1] = *v;
+    }
+    let h = a.lu().solve(&b).expect("corners form a quadrilateral");
+    [[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], 1.0]]
+}
+
+pub fn detection(family: TagFamily, id: u32, corners: [Point; 4]) -> TagDetection {
+    let homography = homography(&corners);
+    let w = homography[2][2];
+    TagDetection {
+        family,
+        id,
+        hamming: 0,
+        decision_margin: 100.0,
+        center: [homography[0][2] / w, homography[1][2] / w],
+        corners,
+        homography,
+    }
+}
+
+/// Where a point given in the frame `camera_from_object` maps from lands in the image.
+pub fn project(intrinsics: &CameraIntrinsics, camera_from_object: &Pose, point: [f64; 3]) -> Point {
+    let [x, y, z] = camera_from_object.transform_point(point);
+    intrinsics.distort_point([intrinsics.fx * x / z + intrinsics.cx, intrinsics.fy * y / z + intrinsics.cy])
+}
+
+/// A detection of every tag on `board`, seen by a camera where the board is at `camera_from_board`.
+pub fn board_detections(board: &TagBoard, intrinsics: &CameraIntrinsics, camera_from_board: &Pose) -> Vec<TagDetection> {
+    board.ids().map(|id| {
+        let corners = board.corners(id).unwrap().map(|c| project(intrinsics, camera_from_board, c));
+        detection(board.family(), id, corners)
+    }).collect()
+}
+
+pub fn assert_pose_close(actual: &Pose, expected: &Pose, tolerance: f64) {
+    let (a, e) = (actual.pos, expected.pos);
+    let translation = ((a.x - e.x).powi(2) + (a.y - e.y).powi(2) + (a.z - e.z).powi(2)).sqrt();
+    let rotation = (actual.rot.matrix() - expected.rot.matrix()).norm();
+    assert!(translation < tolerance && rotation < tolerance, "{:?} != {:?}", actual, expected);
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "3d")]
+mod common;
+
+use apriltag_rs::detector::{Rotation, Translation};
+use apriltag_rs::{BoardPose, CameraIntrinsics, CornerNoise, Covariance, Distortion, Pose, TagBoard, TagFamily};
+use common::{assert_pose_close, detection, project};
+use nalgebra::{DMatrix, Matrix6};
+
+fn board() -> TagBoard {
+    TagBoard::grid(TagFamily::Tag36h11, 2, 2, 0.1, 0.02, 0)
+}
+
+fn board_corners() -> Vec<[f64; 3]> {
+    let board = board();
+    board.ids().flat_map(|id| *board.corners(id).unwrap()).collect()
+}
+
+fn estimate() -> BoardPose {
+    BoardPose {
+        pose: Pose {
+            rot: Rotation::from_roll_pitch_yaw(0.3, -0.2, 0.1),
+            pos: Translation { x: -0.1, y: 0.05, z: 0.6 },
+        },
+        error: 0.0,
+        ids: vec![0, 1, 2, 3],
+    }
+}
+
+// The covariance worked out independently: a numerical Jacobian of the distorted corners with
+// respect to a translation, then a rotation about the camera's axes, applied on the left.
+fn numerical_covariance(corners: &[[f64; 3]], intrinsics: &CameraIntrinsics, pose: &Pose, sigma: f64) -> Matrix6<f64> {
+    let projected = |pose: &Pose| -> Vec<f64> {
+        corners.iter().flat_map(|&c| project(intrinsics, pose, c)).collect()
+    };
+    let perturbed = |i: usize, step: f64| {
+        let mut pose = *pose;
+        match i {
+            0 => pose.pos.x += step,
+            1 => pose.pos.y += step,
+            2 => pose.pos.z += step,
+            _ => {
+                let mut axis = [0.0; 3];
+                axis[i - 3] = 1.0;
+                pose.rot = Rotation::from_axis_angle(axis, step).unwrap() * pose.rot;
+            }
+        }
+        pose
+    };
+    let step = 1e-6;
+    let mut jacobian = DMatrix::<f64>::zeros(corners.len() * 2, 6);
+    for i in 0..6 {
+        let (a, b) = (projected(&perturbed(i, step)), projected(&perturbed(i, -step)));
+        for row in 0..a.len() {
+            jacobian[(row, i)] = (a[row] - b[row]) / (2.0 * step);
+        }
+    }
+    let information = jacobian.transpose() * jacobian / (sigma * sigma);
+    Matrix6::from_fn(|i, j| information[(i, j)]).try_inverse().unwrap()
+}
+
+fn assert_covariance_close(actual: &Covariance, expected: &Matrix6<f64>) {
+    let actual = Matrix6::from_fn(|i, j| actual[i][j]);
+    assert!((actual - expected).norm() < 1e-4 * expected.norm(), "{} != {}", actual, expected);
+}
+
+#[test]
+fn board_covariance_matches_the_projection() {
+    let intrinsics = CameraIntrinsics::new(600.0, 620.0, 320.0, 240.0);
+    let covariance = board().pose_covariance(&estimate(), &intrinsics, &CornerNoise::isotropic(0.5)).unwrap();
+    assert_covariance_close(&covariance, &numerical_covariance(&board_corners(), &intrinsics, &estimate().pose, 0.5));
+
+    // Doubling the noise quadruples the covariance
+    let doubled = board().pose_covariance(&estimate(), &intrinsics, &CornerNoise::isotropic(1.0)).unwrap();
+    for (a, b) in doubled.iter().flatten().zip(covariance.iter().flatten()) {
+        assert!((a - 4.0 * b).abs() <= 1e-9 * a.abs().max(1e-12));
+    }
+}
+
+#[test]
+fn distortion_is_included() {
+    let pinhole = CameraIntrinsics::new(600.0, 620.0, 320.0, 240.0);
+    let distorted = pinhole.with_distortion(Distortion::BrownConrady { k1: -0.3, k2: 0.1, k3: 0.0, p1: 2e-3, p2: -1e-3 });
+    let noise = CornerNoise::isotropic(0.5);
+    let covariance = board().pose_covariance(&estimate(), &distorted, &noise).unwrap();
+    assert_covariance_close(&covariance, &numerical_covariance(&board_corners(), &distorted, &estimate().pose, 0.5));
+
+    // Barrel distortion squeezes the board into fewer pixels, so the pose is less certain
+    let without = board().pose_covariance(&estimate(), &pinhole, &noise).unwrap();
+    assert!(covariance[2][2] > without[2][2]);
+}
+
+#[test]
+fn single_tag_covariance_matches_the_projection() {
+    let intrinsics = CameraIntrinsics::new(600.0, 620.0, 320.0, 240.0);
+    let truth = estimate().pose;
+    let half = 0.05;
+    let corners = [[-half, half, 0.0], [half, half, 0.0], [half, -half, 0.0], [-half, -half, 0.0]];
+    let det = detection(TagFamily::Tag36h11, 0, corners.map(|c| project(&intrinsics, &truth, c)));
+    let estimate = det.estimate_pose_with_covariance(&intrinsics, 2.0 * half, &CornerNoise::isotropic(0.5)).unwrap();
+    assert_pose_close(&estimate.pose, &truth, 1e-4);
+    assert_covariance_close(&estimate.covariance, &numerical_covariance(&corners, &intrinsics, &estimate.pose, 0.5));
+}
+
+#[test]
+fn noise_must_be_positive() {
+    let intrinsics = CameraIntrinsics::new(600.0, 600.0, 320.0, 240.0);
+    assert!(board().pose_covariance(&estimate(), &intrinsics, &CornerNoise::isotropic(0.0)).is_err());
+}
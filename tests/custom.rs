@@ -0,0 +1,70 @@
+use apriltag_rs::{CustomFamily, Detector, Error, RenderOptions, TagFamily};
+
+// tag16h5's bit layout and its first few codes, rebuilt in Rust
+const BIT_X: [i32; 16] = [1, 2, 3, 2, 4, 4, 4, 3, 4, 3, 2, 3, 1, 1, 1, 2];
+const BIT_Y: [i32; 16] = [1, 1, 1, 2, 1, 2, 3, 2, 4, 4, 4, 3, 4, 3, 2, 3];
+const CODES: [u64; 4] = [0x27c8, 0x31b6, 0x3859, 0x569c];
+
+fn family(name: &str) -> CustomFamily {
+    CustomFamily::new(name)
+        .codes(CODES)
+        .bit_layout(&BIT_X, &BIT_Y)
+        .width_at_border(6)
+        .total_width(8)
+}
+
+#[test]
+fn custom_families_are_registered_by_name() {
+    let fam = family("tagRegistered16").build().unwrap();
+    assert_eq!(fam.name(), "tagRegistered16");
+    assert_eq!(fam.to_string().parse::<TagFamily>().unwrap(), fam);
+    assert_eq!(TagFamily::from_name("TAGREGISTERED16"), Some(fam));
+    assert_eq!((fam.code_count(), fam.bit_count(), fam.total_width()), (4, 16, 8));
+    assert!(fam.min_hamming() >= 5);
+
+    // Names are unique, whatever their case, and built-in names are taken
+    assert!(family("tagregistered16").build().is_err());
+    assert!(family("tag16h5").build().is_err());
+}
+
+#[test]
+fn rejects_invalid_families() {
+    assert!(matches!(family("").build(), Err(Error::InvalidConfig { field: "name", .. })));
+    assert!(matches!(
+        family("tagWideCodes").codes(vec![1 << 16]).build(),
+        Err(Error::InvalidConfig { field: "codes", .. })
+    ));
+    assert!(matches!(
+        family("tagOddBits").bit_layout(&BIT_X[..15], &BIT_Y[..15]).build(),
+        Err(Error::InvalidConfig { field: "bit_layout", .. })
+    ));
+    assert!(matches!(
+        family("tagMismatched").bit_layout(&BIT_X, &BIT_Y[..12]).build(),
+        Err(Error::InvalidConfig { field: "bit_layout", .. })
+    ));
+    // The same bits, but with the second and last quarters swapped, so they turn counter-clockwise
+    let swap = |bits: [i32; 16]| [&bits[..4], &bits[12..], &bits[8..12], &bits[4..8]].concat();
+    assert!(matches!(
+        family("tagCounterClockwise").bit_layout(&swap(BIT_X), &swap(BIT_Y)).build(),
+        Err(Error::InvalidConfig { field: "bit_layout", .. })
+    ));
+    assert!(matches!(
+        family("tagNarrow").total_width(7).build(),
+        Err(Error::InvalidConfig { field: "total_width", .. })
+    ));
+}
+
+#[test]
+fn custom_tags_are_detected() {
+    let fam = family("tagDetected16").build().unwrap();
+    let mut detector = Detector::new();
+    detector.add(fam);
+    for id in 0..fam.code_count() {
+        let image = fam.render_with(id, &RenderOptions { scale: 10, quiet_zone: 2 }).unwrap();
+        let detections = detector.detect(image);
+        assert_eq!(detections.len(), 1, "tag {} wasn't detected", id);
+        assert_eq!(detections[0].family, fam);
+        assert_eq!(detections[0].family.name(), "tagDetected16");
+        assert_eq!((detections[0].id, detections[0].hamming), (id, 0));
+    }
+}
@@ -0,0 +1,120 @@
+use apriltag_rs::{Detector, DetectorConfig, Error, QuadThresholdParams, RenderOptions, TagDetection, TagFamily};
+
+#[test]
+fn thread_counts_are_validated() {
+    assert_eq!(Detector::new_with_threads(3).config().threads, 3);
+    assert!(matches!(
+        Detector::try_new_with_threads(0),
+        Err(Error::InvalidConfig { field: "threads", reason: "must be at least 1" })
+    ));
+    assert!(matches!(
+        Detector::try_new_with_threads(u32::MAX),
+        Err(Error::InvalidConfig { field: "threads", reason: "too large" })
+    ));
+    let cfg = DetectorConfig { threads: i32::MAX as u32 + 1, ..Default::default() };
+    assert!(matches!(cfg.validate(), Err(Error::InvalidConfig { reason: "too large", .. })));
+}
+
+#[test]
+fn failed_updates_leave_the_config_alone() {
+    let mut detector = Detector::new();
+    let before = detector.config();
+    let cfg = DetectorConfig { threads: 4, quad_decimate: 0.5, ..before };
+    assert!(detector.set_config(&cfg).is_err());
+    assert_eq!(detector.config(), before);
+    assert!(detector.set_threads(0).is_err());
+    assert_eq!(detector.config(), before);
+
+    let cfg = DetectorConfig { threads: 4, quad_decimate: 1.0, ..before };
+    detector.set_config(&cfg).unwrap();
+    assert_eq!(detector.config(), cfg);
+}
+
+#[test]
+fn raw_detections_convert_to_owned_ones() {
+    let image = TagFamily::Tag25h9.render_with(3, &RenderOptions { scale: 10, quiet_zone: 2 }).unwrap();
+    let mut detector = Detector::new();
+    detector.add(TagFamily::Tag25h9);
+    let raw = detector.detect_raw(image.view(0, 0, image.width(), image.height()));
+    assert_eq!(raw.len(), 1);
+    assert_eq!(raw[0].try_family().unwrap(), TagFamily::Tag25h9);
+
+    let owned = TagDetection::try_from(&raw[0]).unwrap();
+    assert_eq!((owned.family, owned.id), (TagFamily::Tag25h9, 3));
+    assert_eq!(owned.corners, raw[0].corners());
+    assert_eq!(detector.detect(image), vec![owned]);
+}
+
+#[test]
+fn quad_threshold_params_are_validated() {
+    let valid = QuadThresholdParams::default();
+    let cases: [(QuadThresholdParams, &str); 8] = [
+        (QuadThresholdParams { min_cluster_pixels: u32::MAX, ..valid }, "min_cluster_pixels"),
+        (QuadThresholdParams { max_nmaxima: 0, ..valid }, "max_nmaxima"),
+        (QuadThresholdParams { max_nmaxima: u32::MAX, ..valid }, "max_nmaxima"),
+        (QuadThresholdParams { critical_rad: -0.1, ..valid }, "critical_rad"),
+        (QuadThresholdParams { critical_rad: 4.0, ..valid }, "critical_rad"),
+        (QuadThresholdParams { critical_rad: f32::NAN, ..valid }, "critical_rad"),
+        (QuadThresholdParams { max_line_fit_mse: -1.0, ..valid }, "max_line_fit_mse"),
+        (QuadThresholdParams { min_white_black_diff: 256, ..valid }, "min_white_black_diff"),
+    ];
+    let mut detector = Detector::new();
+    let before = detector.quad_threshold_params();
+    for (params, expected) in cases {
+        assert!(matches!(params.validate(), Err(Error::InvalidConfig { field, .. }) if field == expected), "{:?}", params);
+        assert!(detector.set_quad_threshold_params(&params).is_err());
+        assert_eq!(detector.quad_threshold_params(), before);
+    }
+    assert!(matches!(
+        QuadThresholdParams { max_line_fit_mse: f32::INFINITY, ..valid }.validate(),
+        Err(Error::InvalidConfig { field: "max_line_fit_mse", .. })
+    ));
+}
+
+#[test]
+fn quad_threshold_params_round_trip() {
+    let params = QuadThresholdParams {
+        min_cluster_pixels: 12,
+        max_nmaxima: 7,
+        critical_rad: 0.5,
+        max_line_fit_mse: 2.5,
+        min_white_black_diff: 40,
+        deglitch: true,
+    };
+    let mut detector = Detector::new();
+    detector.set_quad_threshold_params(&params).unwrap();
+    assert_eq!(detector.quad_threshold_params(), params);
+    assert_eq!(detector.config().quad_threshold, params);
+}
+
+fn with_homography(homography: [[f64; 3]; 3]) -> TagDetection {
+    TagDetection {
+        family: TagFamily::Tag36h11,
+        id: 0,
+        hamming: 0,
+        decision_margin: 50.0,
+        center: [homography[0][2] / homography[2][2], homography[1][2] / homography[2][2]],
+        corners: [[0.0; 2]; 4],
+        homography,
+    }
+}
+
+#[test]
+fn unproject_inverts_project() {
+    // A tag seen in perspective, so the bottom row of the homography matters
+    let det = with_homography([[40.0, 6.0, 320.0], [-4.0, 35.0, 240.0], [0.02, -0.01, 1.0]]);
+    for p in [[0.0, 0.0], [1.0, -1.0], [-0.7, 0.4], [3.0, 2.5]] {
+        let q = det.unproject(det.project(p).unwrap()).unwrap();
+        assert!((p[0] - q[0]).abs() < 1e-9 && (p[1] - q[1]).abs() < 1e-9, "{:?} != {:?}", p, q);
+    }
+    assert_eq!(det.project([0.0, 0.0]), Some(det.center));
+}
+
+#[test]
+fn singular_homographies_dont_unproject() {
+    // Rank 2: every tag point lands on one line in the image
+    let det = with_homography([[40.0, 80.0, 320.0], [20.0, 40.0, 160.0], [0.0, 0.0, 1.0]]);
+    assert!(det.project([0.5, 0.5]).is_some());
+    assert_eq!(det.unproject([320.0, 160.0]), None);
+    assert_eq!(with_homography([[0.0; 3]; 3]).unproject([1.0, 1.0]), None);
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "3d")]
+
+use apriltag_rs::{CameraIntrinsics, Distortion, undistort_points};
+
+const BROWN_CONRADY: Distortion = Distortion::BrownConrady { k1: -0.28, k2: 0.07, k3: 0.0, p1: 1e-3, p2: -5e-4 };
+const KANNALA_BRANDT: Distortion = Distortion::KannalaBrandt { k1: -0.01, k2: 0.02, k3: -0.005, k4: 1e-3 };
+const POINTS: [[f64; 2]; 5] = [[0.0, 0.0], [0.1, -0.05], [-0.3, 0.2], [0.45, 0.4], [-0.02, -0.6]];
+
+fn assert_close(a: [f64; 2], b: [f64; 2]) {
+    assert!((a[0] - b[0]).abs() < 1e-9 && (a[1] - b[1]).abs() < 1e-9, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn undistortion_inverts_distortion() {
+    for distortion in [BROWN_CONRADY, KANNALA_BRANDT] {
+        for p in POINTS {
+            assert_close(distortion.undistort(distortion.distort(p)).unwrap(), p);
+            assert_close(distortion.distort(distortion.undistort(p).unwrap()), p);
+        }
+    }
+}
+
+#[test]
+fn strong_distortion_is_undistorted() {
+    // Fixed-point iteration alone oscillates towards the corners with these
+    let distortion = Distortion::BrownConrady { k1: -0.4, k2: 0.2, k3: -0.05, p1: 0.0, p2: 0.0 };
+    for i in -10..=10 {
+        for j in -10..=10 {
+            let p = [i as f64 * 0.06, j as f64 * 0.06];
+            if p[0].hypot(p[1]) < 0.6 {
+                let undistorted = distortion.undistort(p).unwrap_or_else(|| panic!("{:?} wasn't undistorted", p));
+                assert_close(distortion.distort(undistorted), p);
+            }
+        }
+    }
+}
+
+#[test]
+fn zero_coefficients_round_trip_exactly() {
+    let brown_conrady = Distortion::BrownConrady { k1: 0.0, k2: 0.0, k3: 0.0, p1: 0.0, p2: 0.0 };
+    for p in POINTS {
+        assert_eq!(brown_conrady.distort(p), p);
+        assert_eq!(brown_conrady.undistort(p), Some(p));
+    }
+
+    // Without coefficients Kannala-Brandt is the equidistant projection, so a point's distance
+    // from the center is the angle it makes with the optical axis
+    let kannala_brandt = Distortion::KannalaBrandt { k1: 0.0, k2: 0.0, k3: 0.0, k4: 0.0 };
+    for p in POINTS.into_iter().chain([[1.2, 0.8], [0.0, 1.5]]) {
+        assert_close(kannala_brandt.distort(kannala_brandt.undistort(p).unwrap()), p);
+        assert_close(kannala_brandt.undistort(kannala_brandt.distort(p)).unwrap(), p);
+    }
+    let [x, y] = kannala_brandt.undistort([0.6, 0.8]).unwrap();
+    assert!(((x * x + y * y).sqrt() - 1f64.tan()).abs() < 1e-12);
+}
+
+#[test]
+fn unreachable_points_are_not_undistorted() {
+    // Strong barrel distortion never pushes a point further out than about 0.27
+    let barrel = Distortion::BrownConrady { k1: -2.0, k2: 0.0, k3: 0.0, p1: 0.0, p2: 0.0 };
+    assert!(barrel.undistort([0.2, 0.0]).is_some());
+    assert_eq!(barrel.undistort([0.5, 0.0]), None);
+
+    // This one folds back at r = 1 and turns outwards again at r = sqrt(2), so anything beyond
+    // 0.6 only comes from past the fold
+    let folded = Distortion::BrownConrady { k1: -0.5, k2: 0.1, k3: 0.0, p1: 0.0, p2: 0.0 };
+    assert!(folded.undistort([0.55, 0.0]).is_some());
+    assert_eq!(folded.undistort([0.7, 0.0]), None);
+
+    // Anything at least 90 degrees off the axis is behind the camera
+    let fisheye = Distortion::KannalaBrandt { k1: 0.0, k2: 0.0, k3: 0.0, k4: 0.0 };
+    assert_eq!(fisheye.undistort([1.6, 0.0]), None);
+    assert_eq!(fisheye.undistort([f64::NAN, 0.0]), None);
+}
+
+#[test]
+fn pixels_are_undistorted_through_the_intrinsics() {
+    let intrinsics = CameraIntrinsics::new(600.0, 610.0, 320.0, 240.0).with_distortion(BROWN_CONRADY);
+    assert_eq!(intrinsics.distortion, BROWN_CONRADY);
+    assert_eq!(intrinsics.pinhole().distortion, Distortion::None);
+
+    let pixels = [[320.0, 240.0], [10.0, 15.0], [600.0, 400.0], [f64::NAN, 240.0]];
+    let undistorted = undistort_points(&intrinsics, &pixels);
+    for (p, q) in pixels.iter().zip(&undistorted).take(3) {
+        assert_close(intrinsics.distort_point(q.unwrap()), *p);
+    }
+    assert_eq!(undistorted[3], None);
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "image")]
+
+use apriltag_rs::{Error, ImageU8};
+use image::{DynamicImage, GrayImage, ImageBuffer, Rgb, RgbImage, Rgba};
+
+#[test]
+fn every_colour_format_uses_the_same_weights() {
+    let (r, g, b) = (200u8, 40u8, 90u8);
+    let expected = ImageU8::from_rgb(1, 1, 3, &[r, g, b]).unwrap();
+
+    let rgb8 = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([r, g, b])));
+    let wide = |v: u8| v as u16 * 257;
+    let rgb16 = DynamicImage::ImageRgb16(ImageBuffer::from_pixel(1, 1, Rgb([wide(r), wide(g), wide(b)])));
+    let rgba16 = DynamicImage::ImageRgba16(ImageBuffer::from_pixel(1, 1, Rgba([wide(r), wide(g), wide(b), 0])));
+    for image in [&rgb8, &rgb16, &rgba16] {
+        let gray = ImageU8::try_from(image).unwrap();
+        assert_eq!(gray.row(0), expected.row(0), "{:?}", image.color());
+    }
+    // BT.601, as in libapriltag's demos. The image crate's own Rec. 709 weights give 78
+    assert_eq!(expected.row(0), Some(&[94][..]));
+}
+
+#[test]
+fn gray_images_convert_both_ways() {
+    let gray = GrayImage::from_raw(3, 2, vec![1, 2, 3, 4, 5, 6]).unwrap();
+    let borrowed = ImageU8::from(&gray);
+    assert_eq!((borrowed.width(), borrowed.height(), borrowed.stride()), (3, 2, 3));
+    assert_eq!(borrowed.row(1), Some(&[4, 5, 6][..]));
+    assert_eq!(GrayImage::from(&borrowed), gray);
+
+    let owned = ImageU8::try_new(3, 2, gray.as_raw().clone()).unwrap();
+    assert_eq!(GrayImage::from(&owned), gray);
+    assert_eq!(GrayImage::from(owned), gray);
+}
+
+#[test]
+fn strided_views_drop_their_padding() {
+    // A 2x2 view into the middle of a 4x3 image, so rows are 4 bytes apart but only 2 are kept
+    let data: Vec<u8> = (0..12).collect();
+    let image = ImageU8::new(4, 3, data);
+    let view = image.view(1, 1, 2, 2);
+    let expected = GrayImage::from_raw(2, 2, vec![5, 6, 9, 10]).unwrap();
+    assert_eq!(GrayImage::from(&view), expected);
+
+    let padded = ImageU8::try_with_stride(2, 2, 4, vec![5, 6, 0, 0, 9, 10]).unwrap();
+    assert_eq!(GrayImage::from(padded), expected);
+}
+
+#[test]
+fn oversized_rows_are_rejected() {
+    // No pixels at all, but a row of them wouldn't fit a u32 stride
+    let wide = DynamicImage::ImageRgb8(RgbImage::new(u32::MAX / 2, 0));
+    assert!(matches!(ImageU8::try_from(&wide), Err(Error::InvalidDimensions { .. })));
+}
@@ -0,0 +1,68 @@
+use apriltag_rs::{Error, RenderOptions, TagFamily};
+
+#[test]
+fn names_round_trip() {
+    for family in TagFamily::ALL {
+        assert_eq!(family.to_string().parse::<TagFamily>().unwrap(), family);
+        assert_eq!(TagFamily::from_name(family.name()), Some(family));
+    }
+}
+
+#[test]
+fn parsing_ignores_case() {
+    for name in ["tag36h11", "TAG36H11", "Tag36h11"] {
+        assert_eq!(name.parse::<TagFamily>().unwrap(), TagFamily::Tag36h11);
+        assert_eq!(TagFamily::from_name(name), Some(TagFamily::Tag36h11));
+    }
+    assert_eq!(TagFamily::from_name("tagstandard41h12"), Some(TagFamily::TagStandard41h12));
+    assert!("tag99h1".parse::<TagFamily>().is_err());
+    assert_eq!(TagFamily::from_name("tag99h1"), None);
+}
+
+#[test]
+fn patterns_match_the_family_layout() {
+    // tag36h11's first code is 0xd7e00984b: 16 white cells inside a black border, all surrounded
+    // by a white ring
+    let cells = TagFamily::Tag36h11.pattern(0).unwrap();
+    assert_eq!(cells.len(), 100);
+    let cell = |x: usize, y: usize| cells[y * 10 + x];
+    for i in 0..10 {
+        assert!(cell(i, 0) && cell(i, 9) && cell(0, i) && cell(9, i));
+    }
+    for i in 1..9 {
+        assert!(!cell(i, 1) && !cell(i, 8) && !cell(1, i) && !cell(8, i));
+    }
+    let white = (2..8).flat_map(|y| (2..8).map(move |x| (x, y))).filter(|&(x, y)| cell(x, y)).count();
+    assert_eq!(white, 0xd7e00984bu64.count_ones() as usize);
+    assert!(TagFamily::Tag36h11.pattern(TagFamily::Tag36h11.code_count()).is_err());
+}
+
+#[test]
+fn renders_follow_the_pattern() {
+    let family = TagFamily::Tag36h11;
+    let cells = family.pattern(0).unwrap();
+    let image = family.render_with(0, &RenderOptions { scale: 3, quiet_zone: 2 }).unwrap();
+    assert_eq!((image.width(), image.height()), (42, 42));
+    for y in 0..42 {
+        let row = image.row(y).unwrap();
+        for (x, &px) in row.iter().enumerate() {
+            let (cx, cy) = (x / 3, y as usize / 3);
+            let white = !(2..12).contains(&cx) || !(2..12).contains(&cy) || cells[(cy - 2) * 10 + cx - 2];
+            assert_eq!(px, if white { 255 } else { 0 }, "pixel ({}, {})", x, y);
+        }
+    }
+    assert_eq!(family.render(0).unwrap().width(), 10);
+}
+
+#[test]
+fn oversized_renders_are_rejected() {
+    let family = TagFamily::Tag36h11;
+    for options in [
+        RenderOptions { scale: 0, quiet_zone: 0 },
+        RenderOptions { scale: u32::MAX, quiet_zone: u32::MAX },
+        RenderOptions { scale: 1, quiet_zone: u32::MAX },
+        RenderOptions { scale: 100_000, quiet_zone: 0 },
+    ] {
+        assert!(matches!(family.render_with(0, &options), Err(Error::InvalidConfig { field: "scale", .. })), "{:?}", options);
+    }
+}
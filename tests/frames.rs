@@ -0,0 +1,209 @@
+#![cfg(feature = "3d")]
+mod common;
+
+use apriltag_rs::detector::{Pose, Rotation, Translation};
+use apriltag_rs::{CameraIntrinsics, CornerNoise, FieldLayout, Frame, TagDetection, TagFamily};
+use common::{assert_pose_close, detection};
+use nalgebra::{DMatrix, Matrix6};
+
+const ALL: [Frame; 4] = [Frame::CameraCv, Frame::RosOptical, Frame::RosBody, Frame::WpiLib];
+
+fn assert_close(a: [f64; 3], b: [f64; 3]) {
+    for i in 0..3 {
+        assert!((a[i] - b[i]).abs() < 1e-12, "{:?} != {:?}", a, b);
+    }
+}
+
+
+#[test]
+fn camera_axes_map_to_body_axes() {
+    // forward, right and down in the camera are forward, -left and -up on the robot
+    for body in [Frame::RosBody, Frame::WpiLib] {
+        assert_close(Frame::CameraCv.convert_point([0.0, 0.0, 1.0], body), [1.0, 0.0, 0.0]);
+        assert_close(Frame::CameraCv.convert_point([1.0, 0.0, 0.0], body), [0.0, -1.0, 0.0]);
+        assert_close(Frame::CameraCv.convert_point([0.0, 1.0, 0.0], body), [0.0, 0.0, -1.0]);
+        assert_close(body.convert_point([0.0, 1.0, 0.0], Frame::RosOptical), [-1.0, 0.0, 0.0]);
+    }
+}
+
+#[test]
+fn matching_conventions_are_identity() {
+    let p = [0.3, -1.2, 4.5];
+    assert_close(Frame::CameraCv.convert_point(p, Frame::RosOptical), p);
+    assert_close(Frame::RosBody.convert_point(p, Frame::WpiLib), p);
+    for frame in ALL {
+        assert_close(frame.convert_point(p, frame), p);
+    }
+}
+
+#[test]
+fn conversions_round_trip() {
+    let pose = Pose {
+        rot: Rotation::from_roll_pitch_yaw(0.4, -0.7, 2.0),
+        pos: Translation { x: 0.2, y: -0.5, z: 3.0 },
+    };
+    for from in ALL {
+        for to in ALL {
+            assert_pose_close(&pose.convert(from, to).convert(to, from), &pose, 1e-12);
+            assert_pose_close(&pose.convert_parent(from, to).convert_parent(to, from), &pose, 1e-12);
+            assert_pose_close(&pose.convert_child(from, to).convert_child(to, from), &pose, 1e-12);
+        }
+    }
+}
+
+#[test]
+fn converted_poses_move_points_consistently() {
+    let pose = Pose {
+        rot: Rotation::from_axis_angle([0.0, 1.0, 0.0], 0.5).unwrap(),
+        pos: Translation { x: 0.1, y: 0.2, z: 2.0 },
+    };
+    let converted = pose.convert(Frame::CameraCv, Frame::WpiLib);
+    let child_point = [0.05, -0.02, 0.0];
+    let expected = Frame::CameraCv.convert_point(pose.transform_point(child_point), Frame::WpiLib);
+    let actual = converted.transform_point(Frame::CameraCv.convert_point(child_point, Frame::WpiLib));
+    assert_close(actual, expected);
+
+    // A tag straight ahead of the camera is at +x in WPILib's convention
+    let ahead = Pose { pos: Translation { x: 0.0, y: 0.0, z: 2.0 }, ..Pose::identity() };
+    let ahead = ahead.convert_parent(Frame::CameraCv, Frame::WpiLib);
+    assert_close([ahead.pos.x, ahead.pos.y, ahead.pos.z], [2.0, 0.0, 0.0]);
+}
+
+const TAG_SIZE: f64 = 0.1651;
+
+// Two tags on the far wall facing back down the field, and one on the left wall facing right
+fn field() -> FieldLayout {
+    let mut layout = FieldLayout::new(TagFamily::Tag36h11, TAG_SIZE);
+    let facing_back = Rotation::from_axis_angle([0.0, 0.0, 1.0], std::f64::consts::PI).unwrap();
+    let facing_right = Rotation::from_axis_angle([0.0, 0.0, 1.0], -std::f64::consts::FRAC_PI_2).unwrap();
+    layout.add_tag(1, Pose { rot: facing_back, pos: Translation { x: 3.0, y: 0.0, z: 1.0 } });
+    layout.add_tag(2, Pose { rot: facing_back, pos: Translation { x: 3.0, y: 0.6, z: 1.2 } });
+    layout.add_tag(3, Pose { rot: facing_right, pos: Translation { x: 2.0, y: 1.5, z: 1.0 } });
+    layout
+}
+
+// Sees every tag from `world_from_camera`, with everything in WPILib's conventions. The corners are
+// worked out from the tag frame directly rather than through `FieldLayout::board`: facing the
+// tag, y points right and z up.
+fn detections(layout: &FieldLayout, intrinsics: &CameraIntrinsics, world_from_camera: &Pose) -> Vec<TagDetection> {
+    let half = TAG_SIZE / 2.0;
+    let corners = [[0.0, -half, -half], [0.0, half, -half], [0.0, half, half], [0.0, -half, half]];
+    let camera_from_world = world_from_camera.inverse();
+    layout.ids().map(|id| {
+        let world_from_tag = layout.tag_pose(id).unwrap();
+        let pixels = corners.map(|corner| {
+            let [x, y, z] = camera_from_world.transform_point(world_from_tag.transform_point(corner));
+            // x forward, y left and z up in the camera, so right is -y and down is -z
+            [intrinsics.fx * -y / x + intrinsics.cx, intrinsics.fy * -z / x + intrinsics.cy]
+        });
+        detection(layout.family, id, pixels)
+    }).collect()
+}
+
+#[test]
+fn localization_finds_the_camera_in_the_world() {
+    let layout = field();
+    let intrinsics = CameraIntrinsics::new(600.0, 600.0, 320.0, 240.0);
+    let truth = Pose {
+        rot: Rotation::from_roll_pitch_yaw(0.0, -0.1, 0.15),
+        pos: Translation { x: 0.5, y: 0.3, z: 0.9 },
+    };
+    let detections: Vec<_> = detections(&layout, &intrinsics, &truth).into_iter().filter(|d| d.id != 3).collect();
+    let localization = layout.localize(&detections, &intrinsics).unwrap();
+
+    assert_pose_close(&localization.combined.pose, &truth, 1e-6);
+    assert_eq!(localization.combined.ids, vec![1, 2]);
+    assert_eq!(localization.per_tag.len(), 2);
+    for estimate in &localization.per_tag {
+        assert_pose_close(&estimate.pose, &truth, 1e-6);
+    }
+}
+
+#[test]
+fn tags_seen_side_on_localize_too() {
+    let layout = field();
+    let intrinsics = CameraIntrinsics::new(500.0, 500.0, 320.0, 240.0);
+    // Looking left, at the tag on the left wall
+    let truth = Pose {
+        rot: Rotation::from_roll_pitch_yaw(0.05, 0.0, std::f64::consts::FRAC_PI_2 - 0.2),
+        pos: Translation { x: 1.8, y: -0.5, z: 0.8 },
+    };
+    let detections: Vec<_> = detections(&layout, &intrinsics, &truth).into_iter().filter(|d| d.id == 3).collect();
+    let localization = layout.localize(&detections, &intrinsics).unwrap();
+    assert_pose_close(&localization.combined.pose, &truth, 1e-6);
+}
+
+// Translation, then the rotation taking `from` to `to` about the world's axes, as in a covariance.
+fn pose_difference(to: &Pose, from: &Pose) -> [f64; 6] {
+    let (axis, angle) = (to.rot * from.rot.inverse()).axis_angle();
+    [
+        to.pos.x - from.pos.x,
+        to.pos.y - from.pos.y,
+        to.pos.z - from.pos.z,
+        axis[0] * angle,
+        axis[1] * angle,
+        axis[2] * angle,
+    ]
+}
+
+#[test]
+fn field_covariance_matches_moving_the_corners() {
+    let layout = field();
+    let intrinsics = CameraIntrinsics::new(600.0, 600.0, 320.0, 240.0);
+    let truth = Pose {
+        rot: Rotation::from_roll_pitch_yaw(0.05, -0.1, 0.15),
+        pos: Translation { x: 0.5, y: 0.3, z: 0.9 },
+    };
+    let detections: Vec<_> = detections(&layout, &intrinsics, &truth).into_iter().filter(|d| d.id != 3).collect();
+    let camera = layout.localize(&detections, &intrinsics).unwrap().combined;
+    let sigma = 0.5;
+    let covariance = layout.pose_covariance(&camera, &intrinsics, &CornerNoise::isotropic(sigma)).unwrap();
+
+    // How far the localized pose moves per pixel, one corner coordinate at a time
+    let step = 1e-3;
+    let mut jacobian = DMatrix::<f64>::zeros(6, detections.len() * 8);
+    for column in 0..detections.len() * 8 {
+        let moved = |step: f64| {
+            let mut moved = detections.clone();
+            moved[column / 8].corners[column % 8 / 2][column % 2] += step;
+            layout.localize(&moved, &intrinsics).unwrap().combined.pose
+        };
+        let (plus, minus) = (pose_difference(&moved(step), &camera.pose), pose_difference(&moved(-step), &camera.pose));
+        for row in 0..6 {
+            jacobian[(row, column)] = (plus[row] - minus[row]) / (2.0 * step);
+        }
+    }
+    let expected = &jacobian * jacobian.transpose() * (sigma * sigma);
+    let expected = Matrix6::from_fn(|i, j| expected[(i, j)]);
+    let actual = Matrix6::from_fn(|i, j| covariance[i][j]);
+    assert!((actual - expected).norm() < 1e-3 * expected.norm(), "{} != {}", actual, expected);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn wpilib_json_round_trips() {
+    const JSON: &str = r#"{
+        "tags": [
+            {"ID": 1, "pose": {"translation": {"x": 3.0, "y": 0.0, "z": 1.0},
+                "rotation": {"quaternion": {"W": 0.0, "X": 0.0, "Y": 0.0, "Z": 1.0}}}},
+            {"ID": 7, "pose": {"translation": {"x": 2.0, "y": 1.5, "z": 1.0},
+                "rotation": {"quaternion": {"W": 0.7071067811865476, "X": 0.0, "Y": 0.0, "Z": -0.7071067811865476}}}}
+        ],
+        "field": {"length": 16.54, "width": 8.21}
+    }"#;
+    let layout = FieldLayout::from_wpilib_json(JSON, TagFamily::Tag36h11, TAG_SIZE).unwrap();
+    assert_eq!((layout.field_length, layout.field_width), (16.54, 8.21));
+    assert_eq!(layout.ids().collect::<Vec<_>>(), vec![1, 7]);
+    let tag = layout.tag_pose(7).unwrap();
+    assert_close([tag.pos.x, tag.pos.y, tag.pos.z], [2.0, 1.5, 1.0]);
+    // Facing right: the tag's x axis points along -y
+    assert_close(tag.rot.rotate([1.0, 0.0, 0.0]), [0.0, -1.0, 0.0]);
+
+    let again = FieldLayout::from_wpilib_json(&layout.to_wpilib_json(), TagFamily::Tag36h11, TAG_SIZE).unwrap();
+    assert_eq!(again, layout);
+    assert_eq!(field(), FieldLayout::from_wpilib_json(&field().to_wpilib_json(), TagFamily::Tag36h11, TAG_SIZE).unwrap());
+
+    assert!(FieldLayout::from_wpilib_json("{}", TagFamily::Tag36h11, TAG_SIZE).is_err());
+    let zero = JSON.replace(r#""W": 0.0, "X": 0.0, "Y": 0.0, "Z": 1.0"#, r#""W": 0.0, "X": 0.0, "Y": 0.0, "Z": 0.0"#);
+    assert!(FieldLayout::from_wpilib_json(&zero, TagFamily::Tag36h11, TAG_SIZE).is_err());
+}
@@ -0,0 +1,152 @@
+use apriltag_rs::generator::{FamilyGenerator, Layout};
+use apriltag_rs::{Detector, Error, ImageU8, RenderOptions, TagDetection};
+use std::collections::HashSet;
+
+// Rotates a code by moving every bit to where its cell lands after turning the tag by 90 degrees,
+// independently of the bit ordering the generator relies on.
+fn rotate_geometrically(layout: &Layout, code: u64) -> u64 {
+    let bits = layout.bits();
+    let n = bits.len();
+    let c = layout.width_at_border() as i32 - 1;
+    let mut out = 0;
+    for (i, &(x, y)) in bits.iter().enumerate() {
+        if code & (1 << (n - 1 - i)) != 0 {
+            let target = (c - y, x);
+            let j = bits.iter().position(|&p| p == target).expect("layout isn't closed under rotation");
+            out |= 1 << (n - 1 - j);
+        }
+    }
+    out
+}
+
+fn rotations(layout: &Layout, code: u64) -> [u64; 4] {
+    let mut out = [code; 4];
+    for i in 1..4 {
+        out[i] = rotate_geometrically(layout, out[i - 1]);
+    }
+    out
+}
+
+fn assert_min_distance(layout: &Layout, codes: &[u64], min_hamming: u32) {
+    for (i, &a) in codes.iter().enumerate() {
+        let rotated = rotations(layout, a);
+        for &r in &rotated[1..] {
+            assert!((a ^ r).count_ones() >= min_hamming, "code {:#x} is too close to its own rotation", a);
+        }
+        for &b in &codes[i + 1..] {
+            for &r in &rotated {
+                assert!((r ^ b).count_ones() >= min_hamming, "codes {:#x} and {:#x} are too close", a, b);
+            }
+        }
+    }
+}
+
+#[test]
+fn classic_layouts_cover_every_cell() {
+    for width in 1..=8 {
+        let layout = Layout::classic(width).unwrap();
+        let cells: HashSet<_> = layout.bits().iter().copied().collect();
+        assert_eq!(cells.len(), (width * width) as usize);
+        assert!(cells.iter().all(|&(x, y)| (1..=width as i32).contains(&x) && (1..=width as i32).contains(&y)));
+        // The generator's own check must accept what it produces
+        Layout::custom(layout.bits().to_vec(), layout.width_at_border(), layout.total_width(), false).unwrap();
+    }
+}
+
+#[test]
+fn generated_codes_respect_min_hamming() {
+    for (width, min_hamming) in [(4, 5), (5, 7), (3, 3)] {
+        let layout = Layout::classic(width).unwrap();
+        let mut generator = FamilyGenerator::new(layout.clone(), min_hamming);
+        generator.max_candidates = 1 << 16;
+        let family = generator.generate().unwrap();
+        assert!(!family.codes.is_empty());
+        assert_min_distance(&layout, &family.codes, min_hamming);
+    }
+}
+
+#[test]
+fn generation_is_deterministic() {
+    let layout = Layout::classic(4).unwrap();
+    let mut generator = FamilyGenerator::new(layout, 5);
+    generator.seed = 42;
+    let first = generator.generate().unwrap();
+    assert_eq!(first, generator.generate().unwrap());
+
+    generator.seed = 43;
+    assert_ne!(first.codes, generator.generate().unwrap().codes);
+}
+
+#[test]
+fn complexity_and_limits() {
+    let layout = Layout::classic(4).unwrap();
+    let mut generator = FamilyGenerator::new(layout.clone(), 3);
+    generator.min_complexity = 12;
+    generator.max_codes = Some(10);
+    let family = generator.generate().unwrap();
+    assert_eq!(family.codes.len(), 10);
+    assert!(family.codes.iter().all(|&code| layout.complexity(code) >= 12));
+    assert_eq!(layout.complexity(0), 0);
+}
+
+#[test]
+fn rejects_unordered_layouts() {
+    let mut bits = Layout::classic(4).unwrap().bits().to_vec();
+    bits.swap(0, 5);
+    assert!(Layout::custom(bits, 6, 8, false).is_err());
+}
+
+#[test]
+fn rejects_counter_clockwise_layouts() {
+    // Mirroring across the diagonal keeps every quarter a rotation of the last, but the other way
+    let bits: Vec<_> = Layout::classic(4).unwrap().bits().iter().map(|&(x, y)| (y, x)).collect();
+    match Layout::custom(bits, 6, 8, false) {
+        Err(Error::InvalidConfig { field: "bits", reason }) => assert!(reason.contains("clockwise")),
+        other => panic!("expected a winding error, got {:?}", other),
+    }
+}
+
+// Turns an image clockwise by 90 degrees.
+fn rotate_clockwise(image: &ImageU8<Vec<u8>>) -> ImageU8<Vec<u8>> {
+    let (w, h) = (image.width(), image.height());
+    let mut data = vec![0; (w * h) as usize];
+    for y in 0..h {
+        for (x, &px) in image.row(y).unwrap().iter().enumerate() {
+            data[(x as u32 * h + (h - 1 - y)) as usize] = px;
+        }
+    }
+    ImageU8::new(h, w, data)
+}
+
+#[test]
+fn custom_layouts_detect_with_the_right_corners() {
+    // The classic layout with each quarter walked in a different order is still clockwise
+    let classic = Layout::classic(4).unwrap();
+    let bits: Vec<_> = classic.bits().chunks(4).flat_map(|q| [q[3], q[1], q[2], q[0]]).collect();
+    let layout = Layout::custom(bits, 6, 8, false).unwrap();
+    let mut generator = FamilyGenerator::new(layout, 5);
+    generator.max_codes = Some(3);
+    let family = generator.generate().unwrap().to_custom_family("tagShuffled16").build().unwrap();
+
+    let mut detector = Detector::new();
+    detector.add(family);
+    let mut image = family.render_with(2, &RenderOptions { scale: 10, quiet_zone: 2 }).unwrap();
+    let detect = |detector: &mut Detector, image: &ImageU8<Vec<u8>>| -> TagDetection {
+        let detections = detector.detect(image.view(0, 0, image.width(), image.height()));
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].id, 2);
+        detections[0]
+    };
+    let mut expected = detect(&mut detector, &image).corners;
+
+    for _ in 0..3 {
+        // Each corner turns with the image, and keeps its place in the list
+        let h = image.height() as f64;
+        expected = expected.map(|[x, y]| [h - y, x]);
+        image = rotate_clockwise(&image);
+        let corners = detect(&mut detector, &image).corners;
+        for (p, q) in corners.iter().zip(&expected) {
+            assert!((p[0] - q[0]).abs() < 1.5 && (p[1] - q[1]).abs() < 1.5, "{:?} != {:?}", corners, expected);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use apriltag_rs::{Detector, Error, ImageU8, RenderOptions, TagFamily};
+
+#[test]
+fn strided_rows_skip_padding() {
+    let data: Vec<u8> = (0..15).collect();
+    let image = ImageU8::with_stride(3, 3, 6, &data[..]);
+    assert_eq!(image.row(0), Some(&[0, 1, 2][..]));
+    assert_eq!(image.row(2), Some(&[12, 13, 14][..]));
+    assert_eq!(image.row(3), None);
+    // The last row doesn't need its padding
+    assert!(ImageU8::try_with_stride(3, 2, 6, &data[..9]).is_ok());
+    assert!(ImageU8::try_with_stride(3, 3, 6, &data[..14]).is_err());
+    assert!(ImageU8::try_with_stride(4, 3, 3, &data[..]).is_err());
+}
+
+#[test]
+fn empty_rows() {
+    let data = [0u8; 4];
+    let image = ImageU8::with_stride(0, 3, 8, &data[..]);
+    for y in 0..3 {
+        assert_eq!(image.row(y), Some(&[][..]));
+    }
+    assert_eq!(image.row(3), None);
+}
+
+#[test]
+fn views_borrow_and_track_origin() {
+    let data: Vec<u8> = (0..100).collect();
+    let image = ImageU8::new(10, 10, &data[..]);
+    let view = image.view(2, 3, 4, 5);
+    assert_eq!((view.width(), view.height(), view.stride()), (4, 5, 10));
+    assert_eq!(view.origin(), (2, 3));
+    assert_eq!(view.row(0), Some(&[32, 33, 34, 35][..]));
+    assert_eq!(view.row(4), Some(&[72, 73, 74, 75][..]));
+
+    let nested = view.view(1, 1, 2, 2);
+    assert_eq!(nested.origin(), (3, 4));
+    assert_eq!(nested.row(1), Some(&[53, 54][..]));
+
+    assert!(image.try_view(8, 0, 3, 1).is_err());
+    assert!(view.try_view(0, 4, 1, 2).is_err());
+    assert!(image.try_view(u32::MAX, 0, 2, 1).is_err());
+    let empty = image.view(10, 10, 0, 0);
+    assert_eq!(empty.row(0), None);
+}
+
+#[test]
+fn detections_in_views_use_frame_coordinates() {
+    let tag = TagFamily::Tag36h11.render_with(0, &RenderOptions { scale: 8, quiet_zone: 2 }).unwrap();
+    let (width, height) = (240, 200);
+    let (x0, y0) = (64, 48);
+    let mut frame = vec![255u8; width * height];
+    for y in 0..tag.height() {
+        let start = (y0 + y as usize) * width + x0;
+        frame[start..start + tag.width() as usize].copy_from_slice(tag.row(y).unwrap());
+    }
+    let frame = ImageU8::new(width as u32, height as u32, &frame[..]);
+
+    let mut detector = Detector::new();
+    detector.add(TagFamily::Tag36h11);
+    let full = detector.detect(frame.view(0, 0, frame.width(), frame.height()));
+    // Offsets that are multiples of the detector's tiles keep the pixels it sees the same
+    let cropped = detector.detect(frame.view(32, 16, 192, 176));
+    assert_eq!(full.len(), 1);
+    assert_eq!(cropped.len(), 1);
+
+    let (a, b) = (&full[0], &cropped[0]);
+    assert_eq!(a.id, b.id);
+    for (p, q) in a.corners.iter().zip(&b.corners).chain([(&a.center, &b.center)]) {
+        assert!((p[0] - q[0]).abs() < 1e-3 && (p[1] - q[1]).abs() < 1e-3, "{:?} != {:?}", p, q);
+    }
+    let projected = b.project([0.0, 0.0]).unwrap();
+    assert!((projected[0] - a.center[0]).abs() < 1e-3 && (projected[1] - a.center[1]).abs() < 1e-3);
+}
+
+#[test]
+fn yuyv_keeps_the_luma() {
+    // Two rows of Y0 U Y1 V pairs, padded to a stride of 10 bytes
+    let data = [
+        10, 128, 20, 128, 30, 128, 40, 128, 0, 0,
+        50, 128, 60, 128, 70, 128, 80, 128,
+    ];
+    let image = ImageU8::from_yuyv(4, 2, 10, &data).unwrap();
+    assert_eq!(image.row(0), Some(&[10, 20, 30, 40][..]));
+    assert_eq!(image.row(1), Some(&[50, 60, 70, 80][..]));
+    assert!(ImageU8::from_yuyv(4, 2, 10, &data[..17]).is_err());
+    assert!(ImageU8::from_yuyv(4, 2, 7, &data).is_err());
+
+    // Pixels come in pairs, so an odd width can't be YUYV, whatever the stride
+    assert!(matches!(ImageU8::from_yuyv(3, 2, 10, &data), Err(Error::InvalidDimensions { width: 3, .. })));
+    assert!(ImageU8::from_yuyv(2, 3, 4, &[1, 128, 2, 128, 3, 128, 4, 128, 5, 128, 6, 128]).is_ok());
+}
+
+#[test]
+fn nv12_borrows_the_luma_plane() {
+    // 3x3 luma with a stride of 4, then the chroma for 2x2 blocks, which rounds the odd sizes up
+    let mut data: Vec<u8> = (0..12).collect();
+    data.extend([128; 4 * 2]);
+    let image = ImageU8::from_nv12(3, 3, 4, &data).unwrap();
+    assert_eq!(image.row(0), Some(&[0, 1, 2][..]));
+    assert_eq!(image.row(2), Some(&[8, 9, 10][..]));
+    assert_eq!(image.row(3), None);
+    // Only the luma plane is needed
+    assert!(ImageU8::from_nv12(3, 3, 4, &data[..11]).is_ok());
+    assert!(ImageU8::from_nv12(3, 3, 4, &data[..10]).is_err());
+}
+
+#[test]
+fn colour_formats_read_their_channels_in_order() {
+    // A pure red pixel, then a pure blue one. BT.601 weights red far more than blue
+    let expected = Some(&[77, 29][..]);
+    let rgb = ImageU8::from_rgb(2, 1, 6, &[255, 0, 0, 0, 0, 255]).unwrap();
+    assert_eq!(rgb.row(0), expected);
+    let bgr = ImageU8::from_bgr(2, 1, 6, &[0, 0, 255, 255, 0, 0]).unwrap();
+    assert_eq!(bgr.row(0), expected);
+    // Alpha is ignored
+    let rgba = ImageU8::from_rgba(2, 1, 8, &[255, 0, 0, 10, 0, 0, 255, 255]).unwrap();
+    assert_eq!(rgba.row(0), expected);
+    let bgra = ImageU8::from_bgra(2, 1, 8, &[0, 0, 255, 10, 255, 0, 0, 255]).unwrap();
+    assert_eq!(bgra.row(0), expected);
+}
@@ -0,0 +1,65 @@
+use apriltag_rs::{Error, ImageU8};
+use std::path::PathBuf;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("apriltag-rs-{}-{}.pnm", std::process::id(), name))
+}
+
+fn load(name: &str, contents: &[u8]) -> Result<ImageU8<Vec<u8>>, Error> {
+    let path = temp_path(name);
+    std::fs::write(&path, contents).unwrap();
+    let image = ImageU8::load_pnm(&path);
+    std::fs::remove_file(&path).unwrap();
+    image
+}
+
+#[test]
+fn saved_images_load_back() {
+    let data: Vec<u8> = (0..=255).cycle().take(7 * 5).collect();
+    let image = ImageU8::new(7, 5, &data[..]);
+    // A view has row padding, which mustn't end up in the file
+    let view = image.view(1, 1, 4, 3);
+    let path = temp_path("round-trip");
+    view.save_pnm(&path).unwrap();
+    let loaded = ImageU8::load_pnm(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let loaded = loaded.unwrap();
+    assert_eq!((loaded.width(), loaded.height()), (4, 3));
+    for y in 0..3 {
+        assert_eq!(loaded.row(y), view.row(y));
+    }
+}
+
+#[test]
+fn ascii_and_wide_samples_are_scaled() {
+    let image = load("ascii", b"P2\n# a comment\n3 1\n15\n0 15 7\n").unwrap();
+    assert_eq!(image.row(0), Some(&[0, 255, 119][..]));
+    let image = load("wide", b"P5 2 1 65535\n\xff\xff\x80\x00").unwrap();
+    assert_eq!(image.row(0), Some(&[255, 128][..]));
+}
+
+#[test]
+fn truncated_rasters_are_rejected() {
+    assert!(matches!(load("short-p5", b"P5 3 2 255\n\x01\x02\x03\x04\x05"), Err(Error::InvalidPnm(_))));
+    assert!(matches!(load("short-p6", b"P6 1 1 255\n\x01\x02"), Err(Error::InvalidPnm(_))));
+    assert!(matches!(load("short-p2", b"P2 2 2 255\n1 2 3"), Err(Error::InvalidPnm(_))));
+}
+
+#[test]
+fn bytes_after_the_raster_are_ignored() {
+    let image = load("long-p5", b"P5 1 1 255\n\x01\x02").unwrap();
+    assert_eq!(image.row(0), Some(&[1][..]));
+    let image = load("two-p5", b"P5 2 1 255\n\x01\x02P5 1 1 255\n\x03").unwrap();
+    assert_eq!((image.width(), image.height()), (2, 1));
+    assert_eq!(image.row(0), Some(&[1, 2][..]));
+}
+
+#[test]
+fn oversized_headers_fail_without_allocating() {
+    // Each of these would need gigabytes or more if the header were trusted
+    assert!(matches!(load("huge-p2", b"P2 100000 100000 255\n1 2 3\n"), Err(Error::InvalidPnm(_))));
+    assert!(matches!(load("huge-p5", b"P5 100000 100000 255\n\x01"), Err(Error::InvalidPnm(_))));
+    assert!(matches!(load("overflow-p5", b"P5 4294967295 4294967295 65535\n\x01"), Err(Error::InvalidPnm(_))));
+    assert!(matches!(load("overflow-p6", b"P6 4294967295 4294967295 255\n\x01"), Err(Error::InvalidPnm(_))));
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "3d")]
+mod common;
+
+use apriltag_rs::detector::{Rotation, Translation};
+use apriltag_rs::{CameraIntrinsics, Pose, PoseEstimate, TagFamily};
+use common::{detection, project};
+
+fn camera() -> CameraIntrinsics {
+    CameraIntrinsics::new(600.0, 600.0, 320.0, 240.0)
+}
+
+// Estimates a tag of side `tag_size` seen at `truth`, with a fixed sub-pixel error on each corner
+fn estimate(truth: &Pose, tag_size: f64) -> PoseEstimate {
+    let noise = [[0.3, -0.2], [-0.25, 0.3], [0.2, 0.25], [-0.3, -0.25]];
+    let mut corners = [[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]]
+        .map(|[x, y]| project(&camera(), truth, [x * tag_size / 2.0, y * tag_size / 2.0, 0.0]));
+    for (corner, noise) in corners.iter_mut().zip(noise) {
+        corner[0] += noise[0];
+        corner[1] += noise[1];
+    }
+    detection(TagFamily::Tag36h11, 0, corners).estimate_pose_candidates(&camera(), tag_size, 50).unwrap()
+}
+
+fn rotation_error(a: &Pose, b: &Pose) -> f64 {
+    (a.rot.matrix() - b.rot.matrix()).norm()
+}
+
+#[test]
+fn ambiguity_is_the_error_ratio() {
+    let pose = Pose::identity();
+    let estimate = PoseEstimate { best: pose, best_error: 0.2, alternate: Some((pose, 0.8)) };
+    assert_eq!(estimate.ambiguity(), 0.25);
+    assert_eq!(PoseEstimate { alternate: None, ..estimate }.ambiguity(), 0.0);
+    assert_eq!(PoseEstimate { best_error: 0.0, alternate: Some((pose, 0.0)), ..estimate }.ambiguity(), 1.0);
+}
+
+#[test]
+fn small_face_on_tags_are_ambiguous() {
+    // Far enough away that perspective can't tell a slight tilt one way from the other
+    let truth = Pose {
+        rot: Rotation::from_roll_pitch_yaw(0.0, 0.15, 0.0),
+        pos: Translation { x: 0.0, y: 0.0, z: 2.5 },
+    };
+    let estimate = estimate(&truth, 0.08);
+    let (_, alternate_error) = estimate.alternate.expect("a face-on tag has two poses");
+    assert!(estimate.best_error <= alternate_error);
+    assert!(estimate.ambiguity() > 0.5, "ambiguity {}", estimate.ambiguity());
+}
+
+#[test]
+fn oblique_tags_are_not_ambiguous() {
+    let truth = Pose {
+        rot: Rotation::from_roll_pitch_yaw(0.3, 0.9, 0.1),
+        pos: Translation { x: 0.05, y: -0.03, z: 0.5 },
+    };
+    let estimate = estimate(&truth, 0.16);
+    assert!(estimate.ambiguity() < 0.2, "ambiguity {}", estimate.ambiguity());
+    assert!(rotation_error(&estimate.best, &truth) < 0.05);
+    if let Some((alternate, alternate_error)) = estimate.alternate {
+        assert!(estimate.best_error < alternate_error);
+        assert!(rotation_error(&estimate.best, &truth) < rotation_error(&alternate, &truth));
+    }
+}
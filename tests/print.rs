@@ -0,0 +1,74 @@
+use apriltag_rs::print::{PageSize, TagSheet};
+use apriltag_rs::{Error, TagFamily};
+
+// Values of every `name="..."` attribute on the SVG elements that start with `element`.
+fn attributes(svg: &str, element: &str, name: &str) -> Vec<f64> {
+    let pattern = format!(" {}=\"", name);
+    svg.lines()
+        .filter(|line| line.starts_with(element))
+        .filter_map(|line| {
+            let start = line.find(&pattern)? + pattern.len();
+            line[start..].split('"').next()?.parse().ok()
+        })
+        .collect()
+}
+
+#[test]
+fn tags_are_split_across_pages() {
+    let sheet = TagSheet::new(TagFamily::Tag36h11, (0..4).collect(), 50.0, PageSize::A4);
+    assert_eq!(sheet.to_svg().unwrap().len(), 1);
+    let sheet = TagSheet::new(TagFamily::Tag36h11, vec![0, 1, 2], 120.0, PageSize::A4);
+    assert_eq!(sheet.to_svg().unwrap().len(), 3);
+
+    let pdf = String::from_utf8(sheet.to_pdf().unwrap()).unwrap();
+    assert!(pdf.starts_with("%PDF-1.4\n"));
+    assert!(pdf.ends_with("%%EOF\n"));
+    assert!(pdf.contains("/Count 3"));
+}
+
+#[test]
+fn rejects_sheets_that_cant_be_printed() {
+    let empty = TagSheet::new(TagFamily::Tag36h11, Vec::new(), 50.0, PageSize::A4);
+    assert!(matches!(empty.to_pdf(), Err(Error::InvalidConfig { field: "ids", .. })));
+    assert!(empty.to_svg().is_err());
+
+    let too_big = TagSheet::new(TagFamily::Tag36h11, vec![0], 500.0, PageSize::A4);
+    assert!(too_big.to_pdf().is_err());
+    let count = TagFamily::Tag16h5.code_count();
+    let bad_id = TagSheet::new(TagFamily::Tag16h5, vec![count], 50.0, PageSize::Letter);
+    assert!(matches!(bad_id.to_svg(), Err(Error::InvalidId { .. })));
+}
+
+#[test]
+fn tags_are_printed_at_their_size() {
+    let sheet = TagSheet::new(TagFamily::Tag36h11, vec![0], 40.0, PageSize::A4);
+    let svg = &sheet.to_svg().unwrap()[0];
+    let cell = 40.0 / TagFamily::Tag36h11.width_at_border() as f64;
+    for width in attributes(svg, "<rect x=\"", "width").into_iter().skip(1) {
+        let cells = width / cell;
+        assert!((cells - cells.round()).abs() < 1e-3, "{} isn't a whole number of cells", width);
+    }
+}
+
+#[test]
+fn labels_clear_the_crop_marks() {
+    let sheet = TagSheet::new(TagFamily::Tag36h11, (0..4).collect(), 30.0, PageSize::A4);
+    let svg = &sheet.to_svg().unwrap()[0];
+    let labels: Vec<(f64, f64)> = attributes(svg, "<text", "x").into_iter().zip(attributes(svg, "<text", "y")).collect();
+    let ys = |name| attributes(svg, "<line", name);
+    let lines: Vec<(f64, f64, f64)> = attributes(svg, "<line", "x1").into_iter()
+        .zip(ys("y1").into_iter().zip(ys("y2")))
+        .map(|(x, (y1, y2))| (x, y1.min(y2), y1.max(y2)))
+        .collect();
+    assert_eq!(labels.len(), 4);
+    assert_eq!(lines.len(), 4 * 8);
+
+    // No mark may cross the band of text to the right of where a label starts
+    for &(x, baseline) in &labels {
+        let top = baseline - 3.0;
+        for &(lx, y1, y2) in &lines {
+            let crosses = lx > x - 0.5 && y1 < baseline + 0.5 && y2 > top - 0.5;
+            assert!(!crosses, "label at ({}, {}) overlaps the mark at x = {} from y = {} to {}", x, baseline, lx, y1, y2);
+        }
+    }
+}
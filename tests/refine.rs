@@ -0,0 +1,82 @@
+#![cfg(feature = "3d")]
+mod common;
+
+use apriltag_rs::detector::{Rotation, Translation};
+use apriltag_rs::{CameraIntrinsics, Loss, Pose, RefineOptions, RefineStatus, TagFamily};
+use common::{detection, project};
+
+const TAG_SIZE: f64 = 0.16;
+// Corners in tag coordinates, in the order libapriltag reports them
+const TAG_CORNERS: [[f64; 2]; 4] = [[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+
+fn camera() -> CameraIntrinsics {
+    CameraIntrinsics::new(900.0, 900.0, 640.0, 360.0)
+}
+
+fn truth() -> Pose {
+    Pose {
+        rot: Rotation::from_roll_pitch_yaw(0.25, -0.4, 0.15),
+        pos: Translation { x: 0.08, y: -0.05, z: 0.8 },
+    }
+}
+
+fn corners(pose: &Pose) -> [[f64; 2]; 4] {
+    TAG_CORNERS.map(|[x, y]| project(&camera(), pose, [x * TAG_SIZE / 2.0, y * TAG_SIZE / 2.0, 0.0]))
+}
+
+// Distance from the truth, with the rotation's error scaled to something comparable
+fn error(pose: &Pose) -> f64 {
+    let (a, e) = (pose.pos, truth().pos);
+    let translation = ((a.x - e.x).powi(2) + (a.y - e.y).powi(2) + (a.z - e.z).powi(2)).sqrt();
+    translation + (pose.rot.matrix() - truth().rot.matrix()).norm() * TAG_SIZE
+}
+
+fn refine(corners: [[f64; 2]; 4], loss: Loss) -> apriltag_rs::RefinedPose {
+    let initial = Pose {
+        rot: Rotation::from_roll_pitch_yaw(0.24, -0.39, 0.16),
+        pos: Translation { x: 0.082, y: -0.049, z: 0.79 },
+    };
+    let options = RefineOptions { loss, ..Default::default() };
+    detection(TagFamily::Tag36h11, 0, corners).refine_pose(&camera(), TAG_SIZE, &initial, &options).unwrap()
+}
+
+#[test]
+fn exact_corners_give_the_true_pose() {
+    let refined = refine(corners(&truth()), Loss::Squared);
+    assert_eq!(refined.status, RefineStatus::Converged);
+    assert!(refined.rms < 1e-6);
+    assert!(error(&refined.pose) < 1e-6);
+}
+
+#[test]
+fn robust_losses_down_weight_an_outlier() {
+    let mut seen = corners(&truth());
+    // A little noise everywhere, and one corner that's badly off
+    for (corner, noise) in seen.iter_mut().zip([[0.2, -0.1], [-0.15, 0.1], [0.1, 0.2], [-0.1, -0.2]]) {
+        corner[0] += noise[0];
+        corner[1] += noise[1];
+    }
+    seen[2][1] += 20.0;
+
+    let squared = refine(seen, Loss::Squared);
+    for loss in [Loss::Huber(1.0), Loss::Cauchy(1.0)] {
+        let robust = refine(seen, loss);
+        assert!(
+            error(&robust.pose) < 0.25 * error(&squared.pose),
+            "{:?}: {} isn't better than {}", loss, error(&robust.pose), error(&squared.pose)
+        );
+        // The outlier is left with most of the error instead of spreading it around
+        let residuals: Vec<f64> = seen.iter().zip(corners(&robust.pose))
+            .map(|(p, q)| ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt())
+            .collect();
+        assert!(residuals[2] > 3.0 * residuals[0].max(residuals[1]).max(residuals[3]), "{:?}", residuals);
+    }
+}
+
+#[test]
+fn loss_scales_are_validated() {
+    for loss in [Loss::Huber(0.0), Loss::Cauchy(-1.0), Loss::Huber(f64::NAN)] {
+        let options = RefineOptions { loss, ..Default::default() };
+        assert!(options.validate().is_err());
+    }
+}
@@ -0,0 +1,116 @@
+#![cfg(feature = "3d")]
+use apriltag_rs::detector::{Pose, Rotation};
+use nalgebra::{Isometry3, Matrix3, Point3, Rotation3, Translation3, Unit, UnitQuaternion, Vector3};
+
+fn assert_matrix_close(a: &Matrix3<f64>, b: &Matrix3<f64>) {
+    assert!((a - b).norm() < 1e-12, "{} != {}", a, b);
+}
+
+#[test]
+fn matrices_convert_in_every_branch() {
+    // Negative traces send the conversion down the branch for the largest diagonal element
+    for axis in [Vector3::x_axis(), Vector3::y_axis(), Vector3::z_axis()] {
+        for angle in [0.3, 2.0, 3.0, std::f64::consts::PI] {
+            let expected = Rotation3::from_axis_angle(&axis, angle);
+            let rotation = Rotation::from_matrix(*expected.matrix()).unwrap();
+            let [w, x, y, z] = rotation.quaternion();
+            assert!(((w * w + x * x + y * y + z * z) - 1.0).abs() < 1e-12);
+            assert_matrix_close(&rotation.matrix(), expected.matrix());
+        }
+    }
+}
+
+#[test]
+fn largest_m11_gives_a_unit_quaternion() {
+    // Half a turn about y: m11 = 1 while m00 = m22 = -1
+    let m = Matrix3::new(
+        -1.0, 0.0, 0.0,
+        0.0, 1.0, 0.0,
+        0.0, 0.0, -1.0,
+    );
+    let rotation = Rotation::from_matrix(m).unwrap();
+    let [w, x, y, z] = rotation.quaternion();
+    assert!(w.abs() < 1e-12 && x.abs() < 1e-12 && (y.abs() - 1.0).abs() < 1e-12 && z.abs() < 1e-12);
+    assert_matrix_close(&rotation.matrix(), &m);
+}
+
+#[test]
+fn nearly_orthogonal_matrices_snap_to_the_closest_rotation() {
+    let expected = Rotation3::from_axis_angle(&Vector3::z_axis(), 0.4);
+    let noise = Matrix3::new(
+        2e-8, -1e-8, 0.0,
+        3e-8, 1e-8, -2e-8,
+        0.0, 1e-8, -3e-8,
+    );
+    let rotation = Rotation::from_matrix(expected.matrix() + noise).unwrap();
+    // The projection moves the matrix no further than the noise that was added
+    assert!((rotation.matrix() - expected.matrix()).norm() <= noise.norm());
+    assert!((rotation.matrix().transpose() * rotation.matrix() - Matrix3::identity()).norm() < 1e-12);
+}
+
+#[test]
+fn sheared_and_mirrored_matrices_are_rejected() {
+    let shear = Matrix3::new(
+        1.0, 0.1, 0.0,
+        0.0, 1.0, 0.0,
+        0.0, 0.0, 1.0,
+    );
+    assert!(Rotation::from_matrix(shear).is_err());
+    assert!(Rotation::from_matrix(Matrix3::identity() * 1.01).is_err());
+    assert!(Rotation::from_matrix(Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, -1.0))).is_err());
+    assert!(Rotation::from_matrix(Matrix3::from_element(f64::NAN)).is_err());
+}
+
+fn about_z(angle: f64) -> Rotation {
+    Rotation::from_axis_angle([0.0, 0.0, 1.0], angle).unwrap()
+}
+
+#[test]
+fn slerp_interpolates_between_the_ends() {
+    let (a, b) = (about_z(0.2), about_z(1.4));
+    assert_matrix_close(&a.slerp(&b, 0.0).matrix(), &a.matrix());
+    assert_matrix_close(&a.slerp(&b, 1.0).matrix(), &b.matrix());
+    assert_matrix_close(&a.slerp(&b, 0.5).matrix(), &about_z(0.8).matrix());
+
+    // The same rotation with its quaternion negated makes no difference
+    let [w, x, y, z] = b.quaternion();
+    let negated = Rotation::from_quaternion(-w, -x, -y, -z).unwrap();
+    assert_matrix_close(&a.slerp(&negated, 0.5).matrix(), &about_z(0.8).matrix());
+}
+
+#[test]
+fn slerp_takes_the_short_way_round() {
+    // The quaternions for these have a negative dot product, and going through 0 would be the
+    // long way
+    let (a, b) = (about_z(170f64.to_radians()), about_z(-170f64.to_radians()));
+    assert_matrix_close(&a.slerp(&b, 0.5).matrix(), &about_z(std::f64::consts::PI).matrix());
+    assert_matrix_close(&a.slerp(&b, 0.25).matrix(), &about_z(175f64.to_radians()).matrix());
+}
+
+#[test]
+fn rotations_convert_to_and_from_nalgebra() {
+    let expected = UnitQuaternion::from_axis_angle(&Unit::new_normalize(Vector3::new(1.0, -2.0, 0.5)), 2.3);
+    let rotation = Rotation::from(expected);
+    let [w, x, y, z] = rotation.quaternion();
+    assert!((Vector3::new(x, y, z) - expected.imag()).norm() < 1e-12 && (w - expected.w).abs() < 1e-12);
+    assert!(UnitQuaternion::from(rotation).angle_to(&expected) < 1e-12);
+
+    let matrix = expected.to_rotation_matrix();
+    assert_matrix_close(&Rotation::from(matrix).matrix(), matrix.matrix());
+    assert_matrix_close(Rotation3::from(Rotation::from(matrix)).matrix(), matrix.matrix());
+}
+
+#[test]
+fn poses_convert_to_and_from_isometries() {
+    let rotation = UnitQuaternion::from_euler_angles(0.3, -0.7, 1.9);
+    let expected = Isometry3::from_parts(Translation3::new(0.5, -1.25, 3.0), rotation);
+    let pose = Pose::from(expected);
+    let point = [0.2, 0.4, -0.6];
+    let moved = expected * Point3::from(point);
+    let [x, y, z] = pose.transform_point(point);
+    assert!((Vector3::new(x, y, z) - moved.coords).norm() < 1e-12);
+
+    let back = Isometry3::from(pose);
+    assert!((back.translation.vector - expected.translation.vector).norm() < 1e-12);
+    assert!(back.rotation.angle_to(&expected.rotation) < 1e-12);
+}
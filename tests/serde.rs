@@ -0,0 +1,85 @@
+#![cfg(feature = "serde")]
+
+use apriltag_rs::{DetectorConfig, QuadThresholdParams, TagDetection, TagFamily};
+
+fn round_trip<T>(value: &T) -> T
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let json = serde_json::to_string(value).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn detector_config() {
+    let cfg = DetectorConfig {
+        threads: 4,
+        quad_decimate: 1.5,
+        refine_edges: true,
+        quad_threshold: QuadThresholdParams {
+            min_white_black_diff: 20,
+            deglitch: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(round_trip(&cfg), cfg);
+}
+
+#[test]
+fn tag_family_uses_canonical_name() {
+    for family in TagFamily::ALL {
+        assert_eq!(round_trip(&family), family);
+    }
+    assert_eq!(serde_json::to_string(&TagFamily::Tag36h11).unwrap(), "\"tag36h11\"");
+    assert_eq!(serde_json::from_str::<TagFamily>("\"tagStandard41h12\"").unwrap(), TagFamily::TagStandard41h12);
+    assert!(serde_json::from_str::<TagFamily>("\"tag99h1\"").is_err());
+}
+
+#[test]
+fn tag_detection() {
+    let det = TagDetection {
+        family: TagFamily::Tag16h5,
+        id: 7,
+        hamming: 1,
+        decision_margin: 42.5,
+        center: [320.0, 240.0],
+        corners: [[300.0, 260.0], [340.0, 260.0], [340.0, 220.0], [300.0, 220.0]],
+        homography: [[20.0, 0.0, 320.0], [0.0, -20.0, 240.0], [0.0, 0.0, 1.0]],
+    };
+    assert_eq!(round_trip(&det), det);
+}
+
+#[cfg(feature = "3d")]
+#[test]
+fn intrinsics_and_pose() {
+    use apriltag_rs::detector::{Pose, PoseEstimate, Translation};
+    use apriltag_rs::{CameraIntrinsics, Distortion};
+
+    let intrinsics = CameraIntrinsics::new(600.0, 610.0, 320.0, 240.0)
+        .with_distortion(Distortion::BrownConrady { k1: -0.1, k2: 0.01, k3: 0.0, p1: 1e-4, p2: -2e-4 });
+    assert_eq!(round_trip(&intrinsics), intrinsics);
+
+    let pose: Pose = serde_json::from_str(
+        r#"{"rot": {"quat": [1.0, 0.0, 0.0, 0.0]}, "pos": {"x": 0.5, "y": -0.25, "z": 2.0}}"#
+    ).unwrap();
+    assert_eq!(pose.pos, Translation { x: 0.5, y: -0.25, z: 2.0 });
+    assert_eq!(round_trip(&pose), pose);
+
+    let estimate = PoseEstimate { best: pose, best_error: 1e-6, alternate: Some((pose, 1e-3)) };
+    assert_eq!(round_trip(&estimate), estimate);
+}
+
+#[cfg(feature = "3d")]
+#[test]
+fn rotations_are_checked() {
+    use apriltag_rs::detector::Rotation;
+
+    let scaled: Rotation = serde_json::from_str(r#"{"quat": [2.0, 0.0, 0.0, 2.0]}"#).unwrap();
+    let half = std::f64::consts::FRAC_1_SQRT_2;
+    let [w, x, y, z] = scaled.quaternion();
+    assert!((w - half).abs() < 1e-12 && x == 0.0 && y == 0.0 && (z - half).abs() < 1e-12);
+
+    assert!(serde_json::from_str::<Rotation>(r#"{"quat": [0.0, 0.0, 0.0, 0.0]}"#).is_err());
+    assert!(serde_json::from_str::<Rotation>(r#"{"quat": [1e-320, 0.0, 0.0, 0.0]}"#).is_err());
+}